@@ -0,0 +1,74 @@
+/// adjust.rs - Color adjustments applied to resized images and video frames
+use image::RgbaImage;
+
+
+/// Color adjustments, the default leaves pixels unchanged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adjustments {
+    pub brightness: f32, // Offset added to every channel, -1 to 1
+    pub contrast: f32,   // Factor around mid gray, 1 = unchanged
+    pub saturation: f32, // Factor around the luma, 0 = gray, 1 = unchanged
+    pub gamma: f32,      // Gamma correction, 1 = unchanged
+    pub invert: bool,    // Negative image
+    pub grayscale: bool, // Drop the colors
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Adjustments {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            gamma: 1.0,
+            invert: false,
+            grayscale: false,
+        }
+    }
+}
+
+impl Adjustments {
+    /// Does applying the adjustments leave the pixels unchanged ?
+    pub fn is_identity(&self) -> bool {
+        *self == Adjustments::default()
+    }
+
+    /// Lookup table for the per-channel adjustments
+    /// (contrast, brightness, gamma then invert)
+    fn channel_table(&self) -> [u8; 256] {
+        let mut table = [0u8; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            let mut v = i as f32 / 255.0;
+            v = (v - 0.5) * self.contrast + 0.5 + self.brightness;
+            v = v.clamp(0.0, 1.0).powf(1.0 / self.gamma);
+            if self.invert {
+                v = 1.0 - v;
+            }
+            *entry = (v * 255.0).round() as u8;
+        }
+        table
+    }
+
+    /// Adjust the colors of an image in place, alpha is kept
+    /// # Parameters
+    /// - `image`: RGBA image object
+    pub fn apply(&self, image: &mut RgbaImage) {
+        if self.is_identity() {
+            return;
+        }
+        let table = self.channel_table();
+        let saturation = if self.grayscale { 0.0 } else { self.saturation };
+        for px in image.pixels_mut() {
+            if saturation != 1.0 {
+                // Rec. 709 luma
+                let luma = 0.2126 * px[0] as f32 + 0.7152 * px[1] as f32 + 0.0722 * px[2] as f32;
+                for channel in 0..3 {
+                    let v = luma + (px[channel] as f32 - luma) * saturation;
+                    px[channel] = v.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+            for channel in 0..3 {
+                px[channel] = table[px[channel] as usize];
+            }
+        }
+    }
+}
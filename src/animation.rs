@@ -0,0 +1,428 @@
+/// animation.rs - Decode and play animated GIF, APNG and WebP without FFmpeg
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, stdout, BufReader, Cursor, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageFormat, RgbImage, RgbaImage};
+use image_webp::{LoopCount, WebPDecoder};
+
+use crate::export;
+use crate::graphics::{frame_cells, frame_layout, write_ansi, RenderOptions, VideoOptions};
+use crate::record::Recorder;
+use crate::{source, term};
+
+
+/// Browsers show the frames of 10ms or less for 100ms, animations are made for it
+const MIN_DELAY: f64 = 0.01;
+const DEFAULT_DELAY: f64 = 0.1;
+
+/// Frames of an animation, composited by the decoder (disposal and blending done)
+pub struct Animation {
+    pub frames: Vec<(RgbaImage, f64)>, // Frame and its delay in seconds
+    pub plays: Option<u32>,            // Times the animation plays, None: forever
+}
+
+impl Animation {
+    /// Frame shown at a given time of the first play, the last one past the end
+    /// # Parameters
+    /// - `time`: Position in seconds
+    pub fn frame_at(&self, time: f64) -> &RgbaImage {
+        let mut end = 0.0;
+        for (frame, delay) in &self.frames {
+            end += delay;
+            if time < end {
+                return frame;
+            }
+        }
+        &self.frames[self.frames.len() - 1].0
+    }
+
+    /// Length of a play in seconds
+    pub fn duration(&self) -> f64 {
+        self.frames.iter().map(|(_, delay)| delay).sum()
+    }
+
+    /// Frames shown between two positions of a play, with the seconds each one stays on screen
+    /// # Parameters
+    /// - `start`: Position of the first frame, the one on screen at this time
+    /// - `end`: Stop at this position, None: at the end of the play
+    pub fn segment(&self, start: f64, end: Option<f64>) -> Vec<(&RgbaImage, f64)> {
+        let end = end.unwrap_or(f64::INFINITY);
+        let mut time = 0.0;
+        let mut segment = Vec::new();
+        for (frame, delay) in &self.frames {
+            let shown = (time + delay).min(end) - time.max(start);
+            if shown > 0.0 {
+                segment.push((frame, shown));
+            }
+            time += delay;
+        }
+        segment
+    }
+}
+
+/// Is the data an animation decoded natively: a GIF with several images,
+/// a PNG with an animation control chunk, or a WebP with the animation flag
+/// # Parameters
+/// - `data`: Encoded image
+pub fn is_animation(data: &[u8]) -> bool {
+    animated(data)
+}
+
+/// Is the file an animation decoded natively, reading only what is needed to tell:
+/// the blocks up to the second image of a GIF, the chunks before the image data of a PNG,
+/// the header of a WebP
+/// # Parameters
+/// - `path`: Path to the image
+pub fn is_animation_file(path: &str) -> bool {
+    File::open(path).map(|file| animated(BufReader::new(file))).unwrap_or(false)
+}
+
+/// Sniff the format, then look for a second GIF image, an APNG control chunk or the WebP flag
+/// # Parameters
+/// - `reader`: Encoded image, from its first byte
+fn animated<R: Read>(mut reader: R) -> bool {
+    let mut header = Vec::new();
+    if reader.by_ref().take(21).read_to_end(&mut header).is_err() {
+        return false;
+    }
+    let reader = header.as_slice().chain(reader);
+    match image::guess_format(&header) {
+        Ok(ImageFormat::Gif) => gif_images(reader, 2).is_ok_and(|images| images > 1),
+        Ok(ImageFormat::Png) => png_chunk(reader, b"acTL").is_some(),
+        Ok(ImageFormat::WebP) => is_animated_webp(&header),
+        _ => false,
+    }
+}
+
+/// Is the data an animated WebP
+/// # Parameters
+/// - `data`: Encoded image, its first 21 bytes are enough
+pub fn is_animated_webp(data: &[u8]) -> bool {
+    // RIFF header, then a VP8X chunk whose flags have the animation bit
+    data.len() > 20 && &data[0..4] == b"RIFF" && &data[8..16] == b"WEBPVP8X"
+        && data[20] & 0x02 != 0
+}
+
+/// Data of a PNG chunk found before the image data
+/// # Parameters
+/// - `reader`: Encoded PNG, from its signature
+/// - `name`: Type of the chunk
+fn png_chunk<R: Read>(mut reader: R, name: &[u8; 4]) -> Option<Vec<u8>> {
+    skip(&mut reader, 8).ok()?; // Signature
+    loop {
+        let mut head = [0; 8]; // Length and type
+        reader.read_exact(&mut head).ok()?;
+        let len = u32::from_be_bytes(head[0..4].try_into().ok()?) as u64;
+        if &head[4..8] == name {
+            let mut data = Vec::new();
+            reader.take(len).read_to_end(&mut data).ok()?;
+            return Some(data).filter(|data| data.len() as u64 == len);
+        }
+        if &head[4..8] == b"IDAT" {
+            return None;
+        }
+        skip(&mut reader, len + 4).ok()?; // Data and CRC
+    }
+}
+
+/// Count the images of a GIF by walking its blocks, without decoding them
+/// # Parameters
+/// - `reader`: Encoded GIF, from its header
+/// - `max`: Stop counting at this number of images
+fn gif_images<R: Read>(mut reader: R, max: usize) -> io::Result<usize> {
+    let mut screen = [0; 13]; // Header and logical screen descriptor
+    reader.read_exact(&mut screen)?;
+    skip_color_table(&mut reader, screen[10])?;
+    let mut images = 0;
+    while images < max {
+        match read_byte(&mut reader)? {
+            0x21 => { // Extension: label, then data sub-blocks
+                read_byte(&mut reader)?;
+                skip_sub_blocks(&mut reader)?;
+            }
+            0x2c => { // Image descriptor, color table, then LZW data sub-blocks
+                images += 1;
+                let mut descriptor = [0; 9];
+                reader.read_exact(&mut descriptor)?;
+                skip_color_table(&mut reader, descriptor[8])?;
+                read_byte(&mut reader)?; // LZW minimum code size
+                skip_sub_blocks(&mut reader)?;
+            }
+            _ => break, // Trailer
+        }
+    }
+    Ok(images)
+}
+
+/// Skip a GIF color table, present when the high bit of the packed fields is set
+/// # Parameters
+/// - `reader`: Encoded GIF, after the descriptor
+/// - `flags`: Packed fields of the descriptor
+fn skip_color_table<R: Read>(reader: &mut R, flags: u8) -> io::Result<()> {
+    if flags & 0x80 == 0 {
+        return Ok(());
+    }
+    skip(reader, 3 << ((flags & 0x07) + 1)) // 2^(n+1) RGB entries
+}
+
+/// Skip GIF data sub-blocks, up to the empty block ending them
+/// # Parameters
+/// - `reader`: Encoded GIF, on the size of the first sub-block
+fn skip_sub_blocks<R: Read>(reader: &mut R) -> io::Result<()> {
+    loop {
+        match read_byte(reader)? {
+            0 => return Ok(()),
+            len => skip(reader, len as u64)?,
+        }
+    }
+}
+
+/// Read a single byte
+/// # Parameters
+/// - `reader`: Source of the byte
+fn read_byte<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Skip bytes without keeping them, fails at the end of the input
+/// # Parameters
+/// - `reader`: Source of the bytes
+/// - `len`: Number of bytes to skip
+fn skip<R: Read>(reader: &mut R, len: u64) -> io::Result<()> {
+    if io::copy(&mut reader.by_ref().take(len), &mut io::sink())? < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Times an animation plays, from the loop count embedded in the file
+/// # Parameters
+/// - `data`: Encoded GIF, APNG or WebP
+pub fn play_count(data: &[u8]) -> Option<u32> {
+    match image::guess_format(data) {
+        // Netscape application extension: number of repetitions after the first play, 0: forever
+        Ok(ImageFormat::Gif) => {
+            let pos = data.windows(11).position(|w| w == b"NETSCAPE2.0" || w == b"ANIMEXTS1.0");
+            match pos.and_then(|pos| data.get(pos + 11..pos + 15)) {
+                Some([3, 1, lo, hi]) => match u16::from_le_bytes([*lo, *hi]) {
+                    0 => None,
+                    repeat => Some(repeat as u32 + 1),
+                },
+                _ => Some(1), // Without the extension, browsers play the animation once
+            }
+        }
+        // Animation control chunk: number of frames, then number of plays, 0: forever
+        Ok(ImageFormat::Png) => match png_chunk(data, b"acTL") {
+            Some(actl) if actl.len() >= 8 => {
+                match u32::from_be_bytes(actl[4..8].try_into().ok()?) {
+                    0 => None,
+                    plays => Some(plays),
+                }
+            }
+            _ => Some(1),
+        },
+        // Animation chunk: number of plays, 0: forever
+        Ok(ImageFormat::WebP) => match WebPDecoder::new(Cursor::new(data)).map(|d| d.loop_count()) {
+            Ok(LoopCount::Times(plays)) => Some(plays.get() as u32),
+            Ok(LoopCount::Forever) => None,
+            Err(_) => Some(1),
+        },
+        _ => Some(1),
+    }
+}
+
+/// Decode all the frames of an animation
+/// # Parameters
+/// - `data`: Encoded GIF, APNG or WebP
+pub fn decode(data: &[u8]) -> Result<Animation, String> {
+    let frames = match image::guess_format(data) {
+        Ok(ImageFormat::Gif) => GifDecoder::new(Cursor::new(data))
+            .map_err(|e| e.to_string())?
+            .into_frames(),
+        Ok(ImageFormat::Png) => PngDecoder::new(Cursor::new(data))
+            .map_err(|e| e.to_string())?
+            .apng()
+            .into_frames(),
+        Ok(ImageFormat::WebP) => return decode_webp(data),
+        _ => return Err("Not an animated GIF, PNG or WebP".to_string()),
+    };
+    let frames = frames.collect_frames().map_err(|e| e.to_string())?;
+    if frames.is_empty() {
+        return Err("Animation has no frames".to_string());
+    }
+    Ok(Animation {
+        frames: frames.into_iter()
+            .map(|frame| {
+                let (numer, denom) = frame.delay().numer_denom_ms();
+                (frame.into_buffer(), frame_delay(numer as f64 / denom.max(1) as f64))
+            })
+            .collect(),
+        plays: play_count(data),
+    })
+}
+
+/// Decode all the frames of an animated WebP, composited on a transparent canvas
+/// # Parameters
+/// - `data`: Encoded WebP
+fn decode_webp(data: &[u8]) -> Result<Animation, String> {
+    let mut decoder = WebPDecoder::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    if !decoder.is_animated() || decoder.num_frames() == 0 {
+        return Err("Animation has no frames".to_string());
+    }
+    let (width, height) = decoder.dimensions();
+    let size = decoder.output_buffer_size().ok_or("Animation is too large")?;
+    let mut frames = Vec::new();
+    for _ in 0..decoder.num_frames() {
+        // RGBA with an alpha channel, RGB without
+        let mut buffer = vec![0; size];
+        let delay = decoder.read_frame(&mut buffer).map_err(|e| e.to_string())?;
+        let frame = if decoder.has_alpha() {
+            RgbaImage::from_raw(width, height, buffer)
+        } else {
+            RgbImage::from_raw(width, height, buffer)
+                .map(|rgb| DynamicImage::ImageRgb8(rgb).to_rgba8())
+        };
+        let frame = frame.ok_or("Frame does not match the canvas")?;
+        frames.push((frame, frame_delay(delay as f64)));
+    }
+    let plays = match decoder.loop_count() {
+        LoopCount::Times(plays) => Some(plays.get() as u32),
+        LoopCount::Forever => None,
+    };
+    Ok(Animation { frames, plays })
+}
+
+/// Delay of a frame in seconds, short delays are shown for 100ms like browsers do
+/// # Parameters
+/// - `ms`: Delay from the file in milliseconds
+fn frame_delay(ms: f64) -> f64 {
+    let delay = ms / 1000.0;
+    if delay <= MIN_DELAY { DEFAULT_DELAY } else { delay }
+}
+
+/// Render frames of an animation to `.ans` files, like `graphics::dump_frames` does for videos
+/// # Parameters
+/// - `animation`: Decoded frames
+/// - `render`: Canvas size, fit and colors
+/// - `dir`: Directory receiving the files
+/// - `frames`: Indices of the rendered frames, from 0, end excluded
+pub fn dump_frames(animation: &Animation, render: &RenderOptions, dir: &str,
+                   frames: Range<u64>) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    let (w, h) = animation.frames[0].0.dimensions();
+    let layout = frame_layout(w, h, render.width, render.height, render.res, render.fit);
+    for (i, (frame, _)) in animation.frames.iter().enumerate() {
+        if frames.contains(&(i as u64)) {
+            let cells = frame_cells(frame, layout, render.res, render.bg, &render.adjust);
+            let path = Path::new(dir).join(format!("frame_{:06}.ans", i));
+            export::export(&path.to_string_lossy(), &cells)?;
+        }
+    }
+    Ok(())
+}
+
+/// Play an animation in the terminal, with the delays and the loop count of the file
+/// # Parameters
+/// - `animation`: Decoded frames
+/// - `render`: Terminal size, fit and colors
+/// - `opts`: Playback options, `loop_video` plays forever, `start` and `end` cut every play
+pub fn play_animation(animation: &Animation, render: &RenderOptions, opts: &VideoOptions) {
+    let &RenderOptions { width, height, res, fit, bg, ref adjust } = render;
+    let plays = if opts.loop_video { None } else { animation.plays };
+    let speed = opts.speed.unwrap_or(1.0);
+
+    // Step mode reads single key presses
+    if opts.step {
+        if let Err(e) = term::enter_raw_mode() {
+            eprintln!("<--step> {}", e);
+            source::exit(7);
+        }
+    }
+    let clear = "\x1b[2J\x1b[H\x1b[?25l"; // Clear screen, move home, hide cursor
+    print!("{}", clear);
+    stdout().flush().ok();
+
+    // All the frames have the size of the canvas of the animation
+    let (w, h) = animation.frames[0].0.dimensions();
+    let layout = frame_layout(w, h, width, height, res, fit);
+    let (crop_w, crop_h) = layout.1;
+    let display_height = if res { crop_h.div_ceil(2) } else { crop_h };
+
+    let mut recorder = match &opts.record {
+        Some(path) => match Recorder::create(path, crop_w, display_height + 1) {
+            Ok(mut recorder) => {
+                recorder.output(0.0, clear.as_bytes()).ok();
+                Some(recorder)
+            }
+            Err(e) => {
+                term::restore();
+                eprintln!("{}", e);
+                source::exit(10);
+            }
+        },
+        None => None,
+    };
+
+    // Frames are rendered during the first play, and reused by the next ones
+    let segment = animation.segment(opts.start.unwrap_or(0.0), opts.end);
+    let mut rendered: Vec<Option<Vec<u8>>> = vec![None; segment.len()];
+    let start_time = Instant::now();
+    let mut time = 0.0; // Seconds on screen since the first frame
+    let mut play = 0;
+    'playback: loop {
+        for (i, (frame, delay)) in segment.iter().enumerate() {
+            let buffer = rendered[i].get_or_insert_with(|| {
+                let mut buffer = Vec::new();
+                write_ansi(&mut buffer, &frame_cells(frame, layout, res, bg, adjust)).ok();
+                write!(buffer, "\x1b[{}F", display_height).ok(); // Move cursor to beginning
+                buffer
+            });
+
+            // Sleep until the frame is due, a slow terminal shows it late rather than skipping it
+            if !opts.step {
+                let target = start_time + Duration::from_secs_f64(time);
+                if let Some(wait) = target.checked_duration_since(Instant::now()) {
+                    sleep(wait);
+                }
+            }
+            let mut out = stdout().lock();
+            out.write_all(buffer).ok();
+            out.flush().ok();
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.output(time, buffer).ok();
+            }
+            time += delay / speed;
+
+            if opts.step && matches!(term::read_key(), term::Key::Char('q') | term::Key::Escape) {
+                break 'playback;
+            }
+        }
+        play += 1;
+        if plays.is_some_and(|plays| play >= plays) {
+            break;
+        }
+    }
+
+    // Keep the last frame on screen for its delay
+    if !opts.step {
+        if let Some(wait) = (start_time + Duration::from_secs_f64(time))
+            .checked_duration_since(Instant::now()) {
+            sleep(wait);
+        }
+    }
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.finish() {
+            eprintln!("{}: {}", opts.record.as_deref().unwrap_or_default(), e);
+        }
+    }
+    term::restore();
+}
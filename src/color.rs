@@ -0,0 +1,182 @@
+/// color.rs - Color management: ICC profiles, EXIF orientation, linear light
+use std::io::Cursor;
+
+use image::imageops::{resize, rotate90, rotate180, rotate270, flip_horizontal, flip_vertical};
+use image::imageops::FilterType;
+use image::{ImageBuffer, Rgba, RgbaImage};
+
+
+/// Extract the embedded ICC profile of a JPEG, PNG or WebP file
+/// # Parameters
+/// - `data`: Encoded image bytes
+pub fn icc_profile(data: &[u8]) -> Option<Vec<u8>> {
+    if data.starts_with(&[0xff, 0xd8]) {
+        jpeg_icc_profile(data)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_icc_profile(data)
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        webp_icc_profile(data)
+    } else {
+        None
+    }
+}
+
+/// ICC profile split across APP2 `ICC_PROFILE` segments
+fn jpeg_icc_profile(data: &[u8]) -> Option<Vec<u8>> {
+    let mut chunks = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xff {
+        let marker = data[pos + 1];
+        if marker == 0xda || marker == 0xd9 { // Start of scan, end of image
+            break;
+        }
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let segment = data.get(pos + 4..pos + 2 + len)?;
+        if marker == 0xe2 && segment.starts_with(b"ICC_PROFILE\0") && segment.len() > 14 {
+            chunks.push((segment[12], &segment[14..])); // Sequence number, data
+        }
+        pos += 2 + len;
+    }
+    if chunks.is_empty() {
+        return None;
+    }
+    chunks.sort_by_key(|&(seq, _)| seq);
+    Some(chunks.into_iter().flat_map(|(_, chunk)| chunk.iter().copied()).collect())
+}
+
+/// Zlib compressed ICC profile of the `iCCP` chunk
+fn png_icc_profile(data: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 8;
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let chunk = data.get(pos + 8..pos + 8 + len)?;
+        match kind {
+            b"iCCP" => {
+                // Profile name, null separator, compression method, profile
+                let name_end = chunk.iter().position(|&b| b == 0)?;
+                let compressed = chunk.get(name_end + 2..)?;
+                return miniz_oxide::inflate::decompress_to_vec_zlib(compressed).ok();
+            }
+            b"IDAT" | b"IEND" => return None,
+            _ => (),
+        }
+        pos += 12 + len; // Length, type, data and CRC
+    }
+    None
+}
+
+/// Raw ICC profile of the `ICCP` chunk
+fn webp_icc_profile(data: &[u8]) -> Option<Vec<u8>> {
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let len = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+        if &data[pos..pos + 4] == b"ICCP" {
+            return data.get(pos + 8..pos + 8 + len).map(|icc| icc.to_vec());
+        }
+        pos += 8 + len + (len & 1); // Chunks are padded to an even size
+    }
+    None
+}
+
+/// Convert an image from its ICC profile to sRGB (e.g. Display P3, Adobe RGB)
+/// # Parameters
+/// - `image`: RGBA image object
+/// - `icc`: Embedded ICC profile
+pub fn convert_to_srgb(image: &mut RgbaImage, icc: &[u8]) {
+    let input = match qcms::Profile::new_from_slice(icc, false) {
+        Some(profile) if !profile.is_sRGB() => profile,
+        _ => return, // Unreadable or already sRGB
+    };
+    let srgb = qcms::Profile::new_sRGB();
+    if let Some(transform) = qcms::Transform::new(&input, &srgb, qcms::DataType::RGBA8,
+                                                  qcms::Intent::Perceptual) {
+        transform.apply(image);
+    }
+}
+
+/// EXIF orientation of an image (1 if unknown)
+/// # Parameters
+/// - `data`: Encoded image bytes
+pub fn exif_orientation(data: &[u8]) -> u32 {
+    exif::Reader::new().read_from_container(&mut Cursor::new(data)).ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+/// Rotate and flip an image according to its EXIF orientation
+/// # Parameters
+/// - `image`: RGBA image object
+/// - `orientation`: EXIF orientation (1 to 8)
+pub fn apply_orientation(image: RgbaImage, orientation: u32) -> RgbaImage {
+    match orientation {
+        2 => flip_horizontal(&image),
+        3 => rotate180(&image),
+        4 => flip_vertical(&image),
+        5 => flip_horizontal(&rotate90(&image)),  // Transpose
+        6 => rotate90(&image),
+        7 => flip_horizontal(&rotate270(&image)), // Transverse
+        8 => rotate270(&image),
+        _ => image,
+    }
+}
+
+/// sRGB transfer function, from encoded [0, 1] to linear light
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Inverse sRGB transfer function, from linear light to encoded [0, 1]
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Resize an image in linear light, with premultiplied alpha, so that
+/// averaged pixels keep their brightness and edges get no halos
+///
+/// Large images are first box averaged to about twice the target size while
+/// converting, so the f32 copy stays small whatever the source resolution
+/// # Parameters
+/// - `image`: RGBA image object
+/// - `w`: Target width in pixels
+/// - `h`: Target height in pixels
+pub fn resample_linear(image: &RgbaImage, w: u32, h: u32) -> RgbaImage {
+    let lut: Vec<f32> = (0..256).map(|v| srgb_to_linear(v as f32 / 255.0)).collect();
+    let (src_w, src_h) = image.dimensions();
+    let fx = (src_w / (w * 2).max(1)).max(1); // Source pixels per box
+    let fy = (src_h / (h * 2).max(1)).max(1);
+    let (box_w, box_h) = (src_w / fx, src_h / fy);
+    let mut linear: ImageBuffer<Rgba<f32>, Vec<f32>> = ImageBuffer::new(box_w, box_h);
+    for (x, y, src) in image.enumerate_pixels() {
+        let alpha = src[3] as f32 / 255.0;
+        let dst = linear.get_pixel_mut((x / fx).min(box_w - 1), (y / fy).min(box_h - 1));
+        dst[0] += lut[src[0] as usize] * alpha;
+        dst[1] += lut[src[1] as usize] * alpha;
+        dst[2] += lut[src[2] as usize] * alpha;
+        dst[3] += alpha;
+    }
+    if fx > 1 || fy > 1 {
+        // Boxes on the right and bottom edges also take the remaining pixels
+        let span = |i: u32, boxes: u32, size: u32, f: u32| if i + 1 == boxes { size - i * f } else { f };
+        for (x, y, dst) in linear.enumerate_pixels_mut() {
+            let count = (span(x, box_w, src_w, fx) * span(y, box_h, src_h, fy)) as f32;
+            dst.0.iter_mut().for_each(|v| *v /= count);
+        }
+    }
+
+    let resized = resize(&linear, w, h, FilterType::Triangle);
+    let mut output = RgbaImage::new(w, h);
+    for (src, dst) in resized.pixels().zip(output.pixels_mut()) {
+        let alpha = src[3].clamp(0.0, 1.0);
+        if alpha <= 0.0 {
+            continue; // Fully transparent
+        }
+        let encode = |v: f32| (linear_to_srgb((v / alpha).clamp(0.0, 1.0)) * 255.0).round() as u8;
+        *dst = Rgba([encode(src[0]), encode(src[1]), encode(src[2]),
+                     (alpha * 255.0).round() as u8]);
+    }
+    output
+}
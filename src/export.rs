@@ -0,0 +1,163 @@
+/// export.rs - Write rendered cells to ANSI text, HTML and SVG files
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::graphics::{write_ansi, Cell, Rgb};
+use crate::term;
+
+
+const SVG_CELL_WIDTH: f64 = 8.0; // Width of a cell in SVG user units
+const SVG_FONT: &str = "monospace";
+
+/// SVG rect in a row of cells: (column, span, top, height, color)
+type Rect = (usize, usize, f64, f64, Rgb);
+
+/// File formats of the rendered output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Ansi,
+    Html,
+    Svg,
+}
+
+impl Format {
+    /// Format matching the extension of a path
+    /// # Parameters
+    /// - `path`: Path to the output file
+    pub fn from_path(path: &str) -> Option<Format> {
+        let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "ans" | "ansi" | "txt" => Some(Format::Ansi),
+            "html" | "htm" => Some(Format::Html),
+            "svg" => Some(Format::Svg),
+            _ => None,
+        }
+    }
+}
+
+/// Write rendered cells to a file, the format follows the extension
+/// # Parameters
+/// - `path`: Path to the output file
+/// - `cells`: Rows of cells
+pub fn export(path: &str, cells: &[Vec<Cell>]) -> Result<(), String> {
+    let format = Format::from_path(path)
+        .ok_or_else(|| format!("{}: Unknown output format (expected .ans, .html or .svg).", path))?;
+    let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut out = BufWriter::new(file);
+    match format {
+        Format::Ansi => write_ansi(&mut out, cells),
+        Format::Html => write_html(&mut out, cells),
+        Format::Svg  => write_svg(&mut out, cells, term::cell_ratio()),
+    }
+    .and_then(|_| out.flush())
+    .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// CSS/SVG notation of a color
+fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+}
+
+/// Escape the characters that are special in HTML and XML
+fn escape(c: char) -> String {
+    match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        _ => c.to_string(),
+    }
+}
+
+/// Write cells as an HTML page: colored spans in a `<pre>`
+/// # Parameters
+/// - `out`: Destination of the page
+/// - `cells`: Rows of cells
+pub fn write_html<W: Write>(out: &mut W, cells: &[Vec<Cell>]) -> std::io::Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">\n</head>\n<body>")?;
+    write!(out, "<pre style=\"font-family: monospace; line-height: 1; letter-spacing: 0\">")?;
+    for row in cells {
+        // Group consecutive cells sharing the same colors in one span
+        let mut start = 0;
+        while start < row.len() {
+            let style = (row[start].fg, row[start].bg);
+            let end = row[start..].iter()
+                .position(|cell| (cell.fg, cell.bg) != style)
+                .map_or(row.len(), |n| start + n);
+            let text: String = row[start..end].iter().map(|cell| escape(cell.glyph)).collect();
+            let mut css = Vec::new();
+            if let Some(fg) = style.0 {
+                css.push(format!("color:{}", hex(fg)));
+            }
+            if let Some(bg) = style.1 {
+                css.push(format!("background-color:{}", hex(bg)));
+            }
+            if css.is_empty() {
+                write!(out, "{}", text)?;
+            } else {
+                write!(out, "<span style=\"{}\">{}</span>", css.join(";"), text)?;
+            }
+            start = end;
+        }
+        writeln!(out)?;
+    }
+    writeln!(out, "</pre>\n</body>\n</html>")?;
+    Ok(())
+}
+
+/// Write cells as an SVG image: block glyphs become rects, other glyphs text
+/// # Parameters
+/// - `out`: Destination of the image
+/// - `cells`: Rows of cells
+/// - `cell_ratio`: Height of a cell divided by its width
+pub fn write_svg<W: Write>(out: &mut W, cells: &[Vec<Cell>], cell_ratio: f64) -> std::io::Result<()> {
+    let cw = SVG_CELL_WIDTH;
+    let ch = SVG_CELL_WIDTH * cell_ratio;
+    let cols = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+    let (w, h) = (cols as f64 * cw, cells.len() as f64 * ch);
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+                   viewBox=\"0 0 {w} {h}\" shape-rendering=\"crispEdges\">", w = w, h = h)?;
+
+    for (i, row) in cells.iter().enumerate() {
+        let y = i as f64 * ch;
+        // Rects of the row merged horizontally, backgrounds under the half blocks
+        let mut layers: [Vec<Rect>; 2] = [Vec::new(), Vec::new()];
+        let mut texts = Vec::new();
+        for (j, cell) in row.iter().enumerate() {
+            let mut parts = Vec::new();
+            if let Some(bg) = cell.bg {
+                parts.push((0, 0.0, ch, bg));
+            }
+            match (cell.glyph, cell.fg) {
+                (' ', _) => (),
+                ('▀', Some(fg)) => parts.push((1, 0.0, ch / 2.0, fg)),
+                ('▄', Some(fg)) => parts.push((1, ch / 2.0, ch / 2.0, fg)),
+                ('X', Some(fg)) if cell.bg == Some(fg) => (), // Full pixel cell
+                ('X', Some(fg)) if cell.bg.is_none() => parts.push((1, 0.0, ch, fg)),
+                (glyph, fg) => texts.push((j, glyph, fg)),
+            }
+            for (layer, top, height, color) in parts {
+                let rects = &mut layers[layer];
+                match rects.iter_mut().rev()
+                    .find(|r| r.2 == top && r.3 == height && r.0 + r.1 == j) {
+                    Some(rect) if rect.4 == color => rect.1 += 1,
+                    _ => rects.push((j, 1, top, height, color)),
+                }
+            }
+        }
+
+        for (col, span, top, height, color) in layers.iter().flatten() {
+            writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                     *col as f64 * cw, y + top, *span as f64 * cw, height, hex(*color))?;
+        }
+        for (col, glyph, fg) in texts {
+            let fill = fg.map(|c| format!(" fill=\"{}\"", hex(c))).unwrap_or_default();
+            writeln!(out, "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"{}\"{}>{}</text>",
+                     col as f64 * cw, y + ch * 0.8, SVG_FONT, ch * 0.8, fill, escape(glyph))?;
+        }
+    }
+    writeln!(out, "</svg>")?;
+    Ok(())
+}
@@ -1,163 +1,474 @@
 /// graphics.rs - Load images and generate ascii data
 /// Author: Sofiane Djerbi (@sofianedjerbi)
-use std::fs::File;
-use std::time::{Duration, Instant};
-use std::io::{stdout, Write, BufReader};
-use std::thread::sleep;
-
-use rodio::{Source, Sink, Decoder, OutputStream};
+use std::io::{stdout, Write};
+use std::ops::Range;
 
 use image::imageops::FilterType;
-use image::imageops::resize;
-use image::{ImageError, RgbaImage, ImageBuffer};
-use image::io::Reader;
+use image::imageops::{resize, replace, crop_imm};
+use image::{ImageError, Rgba, RgbaImage};
+
+use crate::{color, export, source, term};
+use crate::subtitle::SubtitleTrack;
+use crate::adjust::Adjustments;
 
+// Video playback, decoded by FFmpeg
+#[cfg(feature = "video")]
+use std::{path::Path, thread::sleep, time::{Duration, Instant}};
+#[cfg(feature = "video")]
+use image::ImageBuffer;
+#[cfg(feature = "video")]
+use crate::record::Recorder;
+#[cfg(feature = "video")]
+use crate::subtitle::{self, SubtitleDecoder, Subtitles, SUBTITLE_LINES};
+#[cfg(feature = "video")]
 use ffmpeg_next as ffmpeg;
-use ffmpeg::{format, media, codec, software::scaling};
+#[cfg(feature = "video")]
+use ffmpeg::{format, media, codec, filter, software::scaling};
+#[cfg(feature = "video")]
 use ffmpeg::util::frame::video::Video as VideoFrame;
 
+// Audio tracks of videos, played by rodio
+#[cfg(feature = "audio")]
+use std::{fs::File, io::BufReader};
+#[cfg(feature = "audio")]
+use rodio::{Source, Sink, Decoder, OutputStream};
 
-/// Print with colors (r, g, b) on the foreground
-#[macro_export]
-macro_rules! printcf {
-    ($t: expr, $r: expr, $g: expr, $b: expr) => {
-        print!("\x1b[0m\x1b[38;2;{};{};{}m{}", $r, $g, $b, $t);
-    }
+
+/// Error of the features that need FFmpeg, in builds without it
+pub const NO_VIDEO: &str = "built without video support (cargo feature `video`)";
+
+/// Error of <--audio>, in builds without rodio
+pub const NO_AUDIO: &str = "built without audio support (cargo feature `audio`)";
+
+/// An RGB color
+pub type Rgb = (u8, u8, u8);
+
+/// A character cell of the rendered output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub glyph: char,
+    pub fg: Option<Rgb>, // None: terminal default
+    pub bg: Option<Rgb>, // None: terminal default
 }
-/// Print with colors (r, g, b) on the background
-#[macro_export]
-macro_rules! printcb {
-    ($t: expr, $r: expr, $g: expr, $b: expr) => {
-        print!("\x1b[48;2;{};{};{}m{}", $r, $g, $b, $t);
+
+impl Cell {
+    /// Empty cell showing the terminal background
+    pub const EMPTY: Cell = Cell { glyph: ' ', fg: None, bg: None };
+
+    /// Cell of a plain text character
+    pub fn text(glyph: char) -> Cell {
+        Cell { glyph, ..Cell::EMPTY }
     }
 }
 
-/// Print with colors (r, g, b) on both background and foreground
-#[macro_export]
-macro_rules! printca {
-     ($t: expr, $r: expr, $g: expr, $b: expr) => {
-        print!("\x1b[48;2;{r};{g};{b}m\x1b[38;2;{r};{g};{b}m{t}",
-            r=$r, g=$g, b=$b, t=$t);
-    }
+
+/// Load an image
+/// # Parameters
+/// - `path`: Path to the image
+pub fn load_image(path: &str) -> Result<RgbaImage, ImageError> {
+    let data = std::fs::read(path)?;
+    load_image_from_memory(&data)
 }
 
-/// Print a square of a single (r, g, b) color
-#[macro_export]
-macro_rules! printc {
-     ($r: expr, $g: expr, $b: expr) => {
-        printca!("X", $r, $g, $b)
+/// Load an image from an in-memory buffer, converted to sRGB and
+/// rotated according to its EXIF orientation
+/// # Parameters
+/// - `data`: Encoded image bytes
+pub fn load_image_from_memory(data: &[u8]) -> Result<RgbaImage, ImageError> {
+    let mut image = image::load_from_memory(data)?.to_rgba8();
+    if let Some(icc) = color::icc_profile(data) {
+        color::convert_to_srgb(&mut image, &icc);
     }
+    Ok(color::apply_orientation(image, color::exif_orientation(data)))
 }
 
-/// Half-pixel resolution: Print two pixels r, g, b(f/b)
-#[macro_export]
-macro_rules! printhp {
-     ($rf: expr, $gf: expr, $bf: expr,
-      $rb: expr, $gb: expr, $bb: expr) => {
-        print!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀",
-               $rf, $gf, $bf, $rb, $gb, $bb)
-    }
+/// Resize an image
+/// # Parameters
+/// - `image`: RGBA image object
+pub fn resize_image(image: &RgbaImage, w: u32, h: u32) -> RgbaImage {
+    resize(image, w, h, FilterType::Nearest)
 }
 
+/// How a picture is fitted in the canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fit {
+    Contain, // Fit inside the canvas, keeping the aspect ratio
+    Cover,   // Fill the canvas, keeping the aspect ratio (cropped)
+    Stretch, // Fill the canvas, ignoring the aspect ratio
+    None,    // Keep the original size
+}
 
-/// Print a space (empty character)
-#[macro_export]
-macro_rules! printe {
-    () => {
-    print!("\x1b[0m ")
+/// Resize a still image: gamma-correct filtering when shrinking,
+/// nearest neighbour when enlarging to keep pixel art sharp
+/// # Parameters
+/// - `image`: RGBA image object
+pub fn scale_image(image: &RgbaImage, w: u32, h: u32) -> RgbaImage {
+    if w < image.width() || h < image.height() {
+        color::resample_linear(image, w, h)
+    } else {
+        resize_image(image, w, h)
     }
 }
 
+/// Fit a picture in the canvas, returns the target (width, height) in pixels
+/// # Parameters
+/// - `w`: Width of the picture in pixels
+/// - `h`: Height of the picture in pixels
+/// - `width`: Width of the canvas in characters
+/// - `height`: Height of the canvas in characters
+/// - `res`: Are we using the half pixel mode ?
+/// - `fit`: How the picture fills the canvas
+pub fn fit_dimensions(w: u32, h: u32, width: u32, height: u32,
+                      res: bool, fit: Fit) -> (u32, u32) {
+    if w == 0 || h == 0 {
+        return (0, 0);
+    }
+    // Canvas in pixels, and the pixel aspect ratio the picture needs to keep
+    let (max_w, max_h) = if res { (width, height * 2) } else { (width, height) };
+    let cell_ratio = term::cell_ratio(); // Char height/width ratio
+    let aspect_ratio = if res {
+        w as f64 / h as f64 * cell_ratio / 2.0 // Half a char per pixel
+    } else {
+        w as f64 / h as f64 * cell_ratio
+    };
+
+    let (target_w, target_h) = match fit {
+        Fit::None    => (w, h),
+        Fit::Stretch => (max_w, max_h),
+        Fit::Contain | Fit::Cover => {
+            let calc_w = (max_h as f64 * aspect_ratio) as u32;
+            // Contain keeps the tighter constraint, cover the looser one
+            if (calc_w <= max_w) == (fit == Fit::Contain) {
+                (calc_w, max_h)
+            } else {
+                let fit_h = (max_w as f64 / aspect_ratio) as u32;
+                (max_w, fit_h)
+            }
+        }
+    };
+    (target_w.max(1), target_h.max(1))
+}
 
-/// Load an image
+/// Resize a picture for the canvas, cropping the overflow (cover, or none on a large picture)
+/// around the center, the same way as `frame_layout`
 /// # Parameters
-/// - `path`: Path to the image
-pub fn load_image(path: &str) -> Result<RgbaImage, ImageError> {
-    let image = Reader::open(path)?.decode()?;
-    return Ok(image.to_rgba8());
+/// - `image`: RGBA image object
+/// - `width`: Width of the canvas in characters
+/// - `height`: Height of the canvas in characters
+/// - `res`: Are we using the half pixel mode ?
+/// - `fit`: How the picture fills the canvas
+pub fn fit_image(image: &RgbaImage, width: u32, height: u32,
+                 res: bool, fit: Fit) -> RgbaImage {
+    let ((w, h), (crop_w, crop_h)) = frame_layout(image.width(), image.height(),
+                                                  width, height, res, fit);
+    let resized = scale_image(image, w, h);
+    if (crop_w, crop_h) == (w, h) {
+        return resized;
+    }
+    crop_imm(&resized, (w - crop_w) / 2, (h - crop_h) / 2, crop_w, crop_h).to_image()
 }
 
-/// Resize an image
+/// Background that semi-transparent pixels are blended over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    Terminal, // Queried from the terminal, black if unknown
+    Color(u8, u8, u8),
+    Checkerboard,
+}
+
+/// How pictures are laid out and colored in the canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    pub width: u32,          // Width of the canvas in characters
+    pub height: u32,         // Height of the canvas in characters
+    pub res: bool,           // Half pixel mode: two pixels per character
+    pub fit: Fit,            // How the picture fills the canvas
+    pub bg: Background,      // Background for semi-transparent pixels
+    pub adjust: Adjustments, // Color adjustments
+}
+
+// Checkerboard squares size (in pixels) and gray levels
+const CHECKER_SIZE: u32 = 4;
+const CHECKER_COLORS: [u8; 2] = [102, 153];
+
+/// Blend semi-transparent pixels over the background, fully transparent
+/// pixels are kept to let the terminal background show through
 /// # Parameters
 /// - `image`: RGBA image object
-pub fn resize_image(image: &RgbaImage, w: u32, h: u32) -> RgbaImage {
-    return resize(image, w, h, FilterType::Nearest);
+/// - `bg`: Background to blend over
+pub fn blend_alpha(image: &mut RgbaImage, bg: Background) {
+    for (x, y, px) in image.enumerate_pixels_mut() {
+        let alpha = px[3] as u32;
+        if alpha == 0 || alpha == 255 {
+            continue;
+        }
+        let (r, g, b) = match bg {
+            Background::Terminal => term::background_color().unwrap_or((0, 0, 0)),
+            Background::Color(r, g, b) => (r, g, b),
+            Background::Checkerboard => {
+                let c = CHECKER_COLORS[((x / CHECKER_SIZE + y / CHECKER_SIZE) % 2) as usize];
+                (c, c, c)
+            }
+        };
+        for (channel, under) in [r, g, b].iter().enumerate() {
+            let over = px[channel] as u32 * alpha + *under as u32 * (255 - alpha);
+            px[channel] = ((over + 127) / 255) as u8;
+        }
+        px[3] = 255;
+    }
 }
 
-/// Show an image
+/// Convert an image to cells, one pixel per character
 /// # Parameters
 /// - `image`: RGBA image object
-pub fn print_image(image: RgbaImage) {
+pub fn image_cells(image: &RgbaImage) -> Vec<Vec<Cell>> {
+    let mut rows = Vec::with_capacity(image.height() as usize);
     for i in 0..image.height() {
+        let mut row = Vec::with_capacity(image.width() as usize);
         for j in 0..image.width() {
             let px = image.get_pixel(j, i);
-            if (*px)[3] == 0 { // Transparent
-                printe!()
+            if px[3] == 0 { // Transparent
+                row.push(Cell::EMPTY);
             }
             else {
-                printc!((*px)[0], (*px)[1], (*px)[2]);
+                let c = Some((px[0], px[1], px[2]));
+                row.push(Cell { glyph: 'X', fg: c, bg: c });
             }
         }
-        print!("\x1b[0m\n");
+        rows.push(row);
     }
+    rows
 }
 
-/// Show an image: Half pixel mode
+/// Convert an image to cells: Half pixel mode, two pixels per character
 /// # Parameters
 /// - `image`: RGBA image object
-pub fn print_image_hpm(image: RgbaImage) {
-    for i in (0..image.height()-1).step_by(2) {
+pub fn image_cells_hpm(image: &RgbaImage) -> Vec<Vec<Cell>> {
+    let mut rows = Vec::with_capacity(image.height().div_ceil(2) as usize);
+    let transparent = Rgba([0, 0, 0, 0]);
+    for i in (0..image.height()).step_by(2) {
+        let mut row = Vec::with_capacity(image.width() as usize);
         for j in 0..image.width() {
-            let pxu = image.get_pixel(j, i);   // Upper pixel
-            let pxl = image.get_pixel(j, i+1); // Lower pixel
-            if (*pxu)[3] == 0 && (*pxl)[3] == 0 { // Both transparent
-                printe!()
+            let pxu = image.get_pixel(j, i); // Upper pixel
+            let pxl = if i + 1 < image.height() { // Lower pixel, none on the last odd row
+                image.get_pixel(j, i + 1)
+            } else {
+                &transparent
+            };
+            let upper = Some((pxu[0], pxu[1], pxu[2]));
+            let lower = Some((pxl[0], pxl[1], pxl[2]));
+            let cell = if pxu[3] == 0 && pxl[3] == 0 { // Both transparent
+                Cell::EMPTY
             }
-            else if (*pxu)[3] == 0 { // Upper transparent
-                printcf!("▄", (*pxl)[0], (*pxl)[1], (*pxl)[2]);
+            else if pxu[3] == 0 { // Upper transparent
+                Cell { glyph: '▄', fg: lower, bg: None }
             }
-            else if (*pxl)[3] == 0 { // Lower transparent
-                printcf!("▀", (*pxu)[0], (*pxu)[1], (*pxu)[2]);
+            else if pxl[3] == 0 { // Lower transparent
+                Cell { glyph: '▀', fg: upper, bg: None }
             }
             else {
-                printhp!((*pxu)[0], (*pxu)[1], (*pxu)[2],
-                         (*pxl)[0], (*pxl)[1], (*pxl)[2]);
+                Cell { glyph: '▀', fg: upper, bg: lower }
+            };
+            row.push(cell);
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+/// Write cells as ANSI escape codes, one line per row
+/// # Parameters
+/// - `out`: Destination of the escape codes
+/// - `cells`: Rows of cells
+pub fn write_ansi<W: Write>(out: &mut W, cells: &[Vec<Cell>]) -> std::io::Result<()> {
+    for row in cells {
+        let mut style = None; // Colors set by the previous cell
+        for cell in row {
+            if style != Some((cell.fg, cell.bg)) {
+                if cell.fg.is_none() || cell.bg.is_none() {
+                    write!(out, "\x1b[0m")?;
+                }
+                if let Some((r, g, b)) = cell.fg {
+                    write!(out, "\x1b[38;2;{};{};{}m", r, g, b)?;
+                }
+                if let Some((r, g, b)) = cell.bg {
+                    write!(out, "\x1b[48;2;{};{};{}m", r, g, b)?;
+                }
+                style = Some((cell.fg, cell.bg));
             }
+            write!(out, "{}", cell.glyph)?;
         }
-        print!("\x1b[0m\n");
+        writeln!(out, "\x1b[0m")?;
     }
+    Ok(())
+}
+
+/// Write an image as ANSI escape codes
+/// # Parameters
+/// - `out`: Destination of the escape codes
+/// - `image`: RGBA image object
+pub fn write_image<W: Write>(out: &mut W, image: &RgbaImage) -> std::io::Result<()> {
+    write_ansi(out, &image_cells(image))
+}
+
+/// Write an image as ANSI escape codes: Half pixel mode
+/// # Parameters
+/// - `out`: Destination of the escape codes
+/// - `image`: RGBA image object
+pub fn write_image_hpm<W: Write>(out: &mut W, image: &RgbaImage) -> std::io::Result<()> {
+    write_ansi(out, &image_cells_hpm(image))
+}
+
+/// Show an image
+/// # Parameters
+/// - `image`: RGBA image object
+pub fn print_image(image: RgbaImage) {
+    write_image(&mut stdout().lock(), &image).ok();
+}
+
+/// Show an image: Half pixel mode
+/// # Parameters
+/// - `image`: RGBA image object
+pub fn print_image_hpm(image: RgbaImage) {
+    write_image_hpm(&mut stdout().lock(), &image).ok();
 }
 
 /// Process and print an image
 /// # Parameters:
 /// - `file`: Path to the image
-/// - `height`: Height of the image in characters
-/// - `res`: Are we using the half pixel mode ?
-pub fn process_image(file: &str, height: u32, res: bool){
+/// - `render`: Canvas size, fit and colors
+/// - `output`: File receiving the render instead of stdout
+pub fn process_image(file: &str, render: &RenderOptions, output: Option<&str>){
     let raw_img = load_image(file);
     let img = match raw_img {
         Ok(pic) => pic,
         Err(_err) => {
             eprintln!("{}: Unknown file format.", file);
-            std::process::exit(4);
+            source::exit(4);
         },
     };
-    let w = img.width();
-    let h = img.height();
-    if res {
-        let img = resize_image(&img, 2*w*height/h, 2*height);
-        print_image_hpm(img);
+    display_image(img, render, output);
+}
+
+/// Process and print an image read from memory (stdin)
+/// # Parameters:
+/// - `data`: Encoded image bytes
+/// - `render`: Canvas size, fit and colors
+/// - `output`: File receiving the render instead of stdout
+pub fn process_image_data(data: &[u8], render: &RenderOptions, output: Option<&str>){
+    let img = match load_image_from_memory(data) {
+        Ok(pic) => pic,
+        Err(_err) => {
+            eprintln!("-: Unknown file format.");
+            source::exit(4);
+        },
+    };
+    display_image(img, render, output);
+}
+
+/// Resize and print a loaded image
+/// # Parameters:
+/// - `img`: RGBA image object
+/// - `render`: Canvas size, fit and colors
+/// - `output`: File receiving the render instead of stdout
+pub fn display_image(img: RgbaImage, render: &RenderOptions, output: Option<&str>){
+    if img.width() == 0 || img.height() == 0 {
+        return; // Nothing to print
     }
-    else {
-        let img = resize_image(&img, 2*w*height/h, height);
-        print_image(img);
+    let mut img = fit_image(&img, render.width, render.height, render.res, render.fit);
+    render.adjust.apply(&mut img);
+    blend_alpha(&mut img, render.bg);
+    let cells = if render.res { image_cells_hpm(&img) } else { image_cells(&img) };
+    output_cells(&cells, output);
+}
+
+/// Print cells on stdout, or write them to the output file
+/// # Parameters:
+/// - `cells`: Rows of cells
+/// - `output`: File receiving the render instead of stdout
+fn output_cells(cells: &[Vec<Cell>], output: Option<&str>) {
+    match output {
+        Some(path) => {
+            if let Err(e) = export::export(path, cells) {
+                eprintln!("{}", e);
+                source::exit(10);
+            }
+        }
+        None => {
+            write_ansi(&mut stdout().lock(), cells).ok();
+        }
+    }
+}
+
+/// Print images side by side as a contact sheet, filenames underneath
+/// # Parameters:
+/// - `files`: Paths to the images
+/// - `cols`: Number of thumbnails per row
+/// - `cell_height`: Height of a grid cell in characters (label included)
+/// - `render`: Canvas width and colors, thumbnails always fit inside their cell
+/// - `output`: File receiving the render instead of stdout
+pub fn process_grid(files: &[String], cols: u32, cell_height: u32,
+                    render: &RenderOptions, output: Option<&str>) {
+    let &RenderOptions { width, res, bg, ref adjust, .. } = render;
+    let cell_w = width / cols;
+    if cell_w < 2 || cell_height < 2 {
+        eprintln!("Grid cells are too small for this canvas.");
+        source::exit(8);
+    }
+    let thumb_w = cell_w - 1;       // Keep a column between thumbnails
+    let thumb_h = cell_height - 1;  // Keep a line for the filename
+    let canvas_h = if res { thumb_h * 2 } else { thumb_h };
+
+    // Load thumbnails, skipping anything that is not an image
+    let mut thumbs = Vec::new();
+    for file in files {
+        match load_image(file) {
+            Ok(img) => {
+                let (w, h) = fit_dimensions(img.width(), img.height(),
+                                            thumb_w, thumb_h, res, Fit::Contain);
+                let name = std::path::Path::new(file).file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| file.clone());
+                let mut thumb = scale_image(&img, w, h);
+                adjust.apply(&mut thumb);
+                blend_alpha(&mut thumb, bg);
+                thumbs.push((thumb, name));
+            }
+            Err(_err) => eprintln!("{}: Unknown file format, skipped.", file),
+        }
     }
+
+    let mut cells = Vec::new();
+    for row in thumbs.chunks(cols as usize) {
+        // Compose the row on a single transparent canvas
+        let mut canvas = RgbaImage::new(cols * cell_w, canvas_h);
+        let mut labels = String::new();
+        for (i, (thumb, name)) in row.iter().enumerate() {
+            let x = i as u32 * cell_w + (thumb_w - thumb.width()) / 2;
+            replace(&mut canvas, thumb, x, 0);
+
+            // Center the filename, truncated to the cell width
+            let name: String = name.chars().take(thumb_w as usize).collect();
+            let pad = thumb_w as usize - name.chars().count();
+            labels.push_str(&format!("{}{}{} ", " ".repeat(pad / 2), name,
+                                     " ".repeat(pad - pad / 2)));
+        }
+
+        if res {
+            cells.extend(image_cells_hpm(&canvas));
+        } else {
+            cells.extend(image_cells(&canvas));
+        }
+        cells.push(labels.trim_end().chars().map(Cell::text).collect());
+    }
+    output_cells(&cells, output);
 }
 
 /// Convert FFmpeg video frame to RgbaImage
 /// # Parameters
 /// - `frame`: FFmpeg video frame
 /// - `scaler`: FFmpeg scaler context
+#[cfg(feature = "video")]
 fn frame_to_rgba(frame: &VideoFrame, scaler: &mut scaling::Context) -> Result<RgbaImage, String> {
     let mut rgb_frame = VideoFrame::empty();
     scaler.run(frame, &mut rgb_frame)
@@ -188,19 +499,32 @@ fn frame_to_rgba(frame: &VideoFrame, scaler: &mut scaling::Context) -> Result<Rg
 /// Extract audio source from video using FFmpeg decoder
 /// # Parameters
 /// - `file`: Path to the file
-fn extract_audio(file: &str) -> Result<Decoder<BufReader<File>>, String> {
+/// - `opts`: Playback options: audio track, segment and speed
+#[cfg(feature = "audio")]
+fn extract_audio(file: &str, opts: &VideoOptions) -> Result<Decoder<BufReader<File>>, String> {
     // Create temp audio file
     let temp_audio = format!("/tmp/batimg_audio_{}.mp3", std::process::id());
 
-    // Extract audio using FFmpeg
-    let output = std::process::Command::new("ffmpeg")
-        .arg("-y")
-        .arg("-i")
+    // Extract audio using FFmpeg, cut to the played segment
+    // The cut is an input option: positions of the source, before atempo changes the timing
+    let mut command = std::process::Command::new("ffmpeg");
+    command.arg("-y");
+    if let Some(start) = opts.start {
+        command.arg("-ss").arg(start.to_string());
+    }
+    if let Some(end) = opts.end {
+        command.arg("-to").arg(end.to_string());
+    }
+    command.arg("-i")
         .arg(file)
         .arg("-q:a")
-        .arg("0")
+        .arg("0");
+    if let Some(speed) = opts.speed.filter(|&speed| speed != 1.0) {
+        command.arg("-filter:a").arg(atempo(speed)); // Keeps the pitch
+    }
+    let output = command
         .arg("-map")
-        .arg("a")
+        .arg(opts.audio_track.map_or("a".to_string(), |n| format!("0:a:{}", n)))
         .arg(&temp_audio)
         .output()
         .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
@@ -217,118 +541,673 @@ fn extract_audio(file: &str) -> Result<Decoder<BufReader<File>>, String> {
         .map_err(|e| format!("Failed to decode audio: {}", e))
 }
 
+/// Audio track of a video, extracted by FFmpeg and played by rodio
+#[cfg(feature = "audio")]
+struct AudioPlayer {
+    _stream: OutputStream, // Output device, playback stops when it is dropped
+    sink: Sink,
+}
+
+#[cfg(feature = "audio")]
+impl AudioPlayer {
+    /// Open the output device and extract the audio, None without device or audio track
+    /// # Parameters
+    /// - `file`: Path to the file
+    /// - `opts`: Playback options: audio track, segment and speed
+    fn open(file: &str, opts: &VideoOptions) -> Option<AudioPlayer> {
+        let (stream, stream_handle) = OutputStream::try_default().ok()?;
+        let sink = Sink::try_new(&stream_handle).ok()?;
+        extract_audio(file, opts).ok()?;
+        Some(AudioPlayer { _stream: stream, sink })
+    }
+
+    /// Play the extracted audio from its start, returns whether it started
+    fn play(&self) -> bool {
+        let audio_file = File::open(format!("/tmp/batimg_audio_{}.mp3", std::process::id()));
+        match audio_file.map(|file| Decoder::new(BufReader::new(file))) {
+            Ok(Ok(decoder)) => {
+                self.sink.append(decoder.repeat_infinite());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Builds without the `audio` feature play videos silently
+#[cfg(all(feature = "video", not(feature = "audio")))]
+struct AudioPlayer;
+
+#[cfg(all(feature = "video", not(feature = "audio")))]
+impl AudioPlayer {
+    fn open(_file: &str, _opts: &VideoOptions) -> Option<AudioPlayer> {
+        None
+    }
+
+    fn play(&self) -> bool {
+        false
+    }
+}
+
+/// FFmpeg filter changing the tempo of audio, each atempo stays within 0.5 to 2
+/// # Parameters
+/// - `speed`: Playback rate, 1 is realtime
+#[cfg(feature = "audio")]
+fn atempo(mut speed: f64) -> String {
+    let mut filters = Vec::new();
+    while speed < 0.5 {
+        filters.push("atempo=0.5".to_string());
+        speed /= 0.5;
+    }
+    while speed > 2.0 {
+        filters.push("atempo=2".to_string());
+        speed /= 2.0;
+    }
+    filters.push(format!("atempo={}", speed));
+    filters.join(",")
+}
+
 /// Cleanup temp audio file
 pub fn clean_tmp_files(){
     std::fs::remove_file(format!("/tmp/batimg_audio_{}.mp3", std::process::id())).ok();
 }
 
-/// Print a video using native FFmpeg decoder (no disk I/O, no subprocess spawning)
+/// Opened video stream of an input
+#[cfg(feature = "video")]
+struct VideoInput {
+    ictx: format::context::Input,
+    index: usize,                   // Index of the video stream
+    decoder: FrameDecoder,
+    scaler: scaling::Context,       // Conversion of the frames to RGBA
+}
+
+/// Video decoder, followed by the `--vf` filter graph if any
+#[cfg(feature = "video")]
+struct FrameDecoder {
+    decoder: codec::decoder::Video,
+    filter: Option<filter::Graph>, // From the "in" buffer to the "out" sink, RGBA frames
+    spec: Option<String>,          // Description of the graph, rebuilt on flush
+    stream_tb: ffmpeg::Rational,   // Time base of the video stream, given to the graph
+    output: GraphOutput,           // Frames out of the graph, those of the stream without one
+}
+
+/// Properties of the frames out of a filter graph, filters like `scale`, `settb` or `fps` change them
+#[cfg(feature = "video")]
+struct GraphOutput {
+    width: u32,
+    height: u32,
+    time_base: ffmpeg::Rational,  // Of the timestamps
+    frame_rate: ffmpeg::Rational, // 0/1 when unknown or variable
+}
+
+#[cfg(feature = "video")]
+impl FrameDecoder {
+    fn width(&self) -> u32 {
+        self.output.width
+    }
+
+    fn height(&self) -> u32 {
+        self.output.height
+    }
+
+    fn id(&self) -> codec::Id {
+        self.decoder.id()
+    }
+
+    /// Seconds per unit of the timestamps of the frames
+    fn time_base(&self) -> f64 {
+        self.output.time_base.0 as f64 / self.output.time_base.1 as f64
+    }
+
+    /// Frame rate out of the filter graph, 0/1 without one
+    fn frame_rate(&self) -> ffmpeg::Rational {
+        self.output.frame_rate
+    }
+
+    fn send_packet(&mut self, packet: &ffmpeg::Packet) -> Result<(), ffmpeg::Error> {
+        self.decoder.send_packet(packet)
+    }
+
+    fn send_eof(&mut self) -> Result<(), ffmpeg::Error> {
+        self.decoder.send_eof()
+    }
+
+    /// Drop the frames buffered before a seek, the graph is rebuilt to reset stateful filters
+    fn flush(&mut self) {
+        self.decoder.flush();
+        if let Some(spec) = &self.spec {
+            if let Ok((graph, _)) = filter_graph(&self.decoder, self.stream_tb, spec) {
+                self.filter = Some(graph);
+            }
+        }
+    }
+
+    /// Next decoded frame, filtered
+    /// # Parameters
+    /// - `frame`: Receives the frame
+    fn receive_frame(&mut self, frame: &mut VideoFrame) -> Result<(), ffmpeg::Error> {
+        let graph = match self.filter.as_mut() {
+            Some(graph) => graph,
+            None => return self.decoder.receive_frame(frame),
+        };
+        loop {
+            // The sink moves its frame into an empty one
+            let mut filtered = VideoFrame::empty();
+            match graph.get("out").unwrap().sink().frame(&mut filtered) {
+                Ok(()) => {
+                    *frame = filtered;
+                    return Ok(());
+                }
+                Err(ffmpeg::Error::Other { errno: libc::EAGAIN }) => (), // Needs more input
+                Err(e) => return Err(e),
+            }
+            let mut decoded = VideoFrame::empty();
+            match self.decoder.receive_frame(&mut decoded) {
+                Ok(()) => graph.get("in").unwrap().source().add(&decoded)?,
+                Err(ffmpeg::Error::Eof) => graph.get("in").unwrap().source().flush()?,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Build a libavfilter graph taking the decoded frames, returns it with the properties of its output
 /// # Parameters
-/// - `file`: Path to the video file
-/// - `width`: Width of the terminal in characters
-/// - `height`: Height of the terminal in characters
-/// - `audio`: Are we playing the audio?
-/// - `res`: Are we using the half pixel mode?
-/// - `loop_video`: Loop the video?
-/// - `sync`: Activate realtime syncing?
-/// - `debug`: Print debug info?
-pub fn process_video(file: &str, width: u32, height: u32, audio: bool,
-                     res: bool, loop_video: bool, sync: bool,
-                     debug: bool) {
-    // Clear screen and hide cursor FIRST (before any processing)
-    print!("\x1b[2J");        // Clear entire screen
-    print!("\x1b[H");         // Move cursor to home position
-    print!("\x1b[?25l");      // Hide cursor
-    stdout().flush().unwrap();
+/// - `decoder`: Decoder feeding the graph
+/// - `time_base`: Time base of the video stream
+/// - `spec`: Filtergraph description, e.g. `crop=640:360,eq=contrast=1.2`
+#[cfg(feature = "video")]
+fn filter_graph(decoder: &codec::decoder::Video, time_base: ffmpeg::Rational,
+                spec: &str) -> Result<(filter::Graph, GraphOutput), String> {
+    let err = |e: ffmpeg::Error| format!("Invalid filter graph '{}': {}", spec, e);
+    let mut graph = filter::Graph::new();
+    let aspect = match decoder.aspect_ratio() {
+        ffmpeg::Rational(num, den) if num > 0 && den > 0 => (num, den),
+        _ => (1, 1), // Unknown: square pixels
+    };
+    let args = format!("video_size={}x{}:pix_fmt={}:time_base={}/{}:pixel_aspect={}/{}",
+                       decoder.width(), decoder.height(),
+                       decoder.format().descriptor().map_or("none", |d| d.name()),
+                       time_base.0, time_base.1, aspect.0, aspect.1);
+    let buffer = filter::find("buffer").ok_or("FFmpeg has no buffer filter")?;
+    let buffersink = filter::find("buffersink").ok_or("FFmpeg has no buffersink filter")?;
+    graph.add(&buffer, "in", &args).map_err(err)?;
+    graph.add(&buffersink, "out", "").map_err(err)?;
 
-    // Initialize FFmpeg and suppress log output
-    ffmpeg::init().expect("Failed to initialize FFmpeg");
-    ffmpeg::util::log::set_level(ffmpeg::util::log::Level::Quiet);
+    // Frames leave the graph in RGBA, whatever the filters output
+    graph.output("in", 0).and_then(|parser| parser.input("out", 0))
+        .and_then(|parser| parser.parse(&format!("{},format=rgba", spec)))
+        .map_err(err)?;
+    graph.validate().map_err(err)?;
 
-    /*** OPEN VIDEO FILE ***/
-    let mut ictx = format::input(&file)
-        .expect("Failed to open video file");
+    let mut sink = graph.get("out").unwrap();
+    let (w, h, frame_rate) = unsafe {
+        (ffmpeg::ffi::av_buffersink_get_w(sink.as_ptr()), ffmpeg::ffi::av_buffersink_get_h(sink.as_ptr()),
+         ffmpeg::ffi::av_buffersink_get_frame_rate(sink.as_ptr()))
+    };
+    let output = GraphOutput {
+        width: w as u32,
+        height: h as u32,
+        time_base: sink.sink().time_base(),
+        frame_rate: frame_rate.into(),
+    };
+    Ok((graph, output))
+}
 
-    // Find video stream
-    let video_stream_index = ictx.streams()
-        .best(media::Type::Video)
-        .expect("No video stream found")
-        .index();
+/// Demuxer options of network streams: small probe, no buffering, timeouts
+/// # Parameters
+/// - `url`: URL of the stream
+#[cfg(feature = "video")]
+fn live_options(url: &str) -> ffmpeg::Dictionary<'static> {
+    let mut options = ffmpeg::Dictionary::new();
+    options.set("fflags", "nobuffer");
+    options.set("flags", "low_delay");
+    options.set("probesize", "500000");
+    options.set("analyzeduration", "1000000"); // 1s
+    options.set("rw_timeout", "10000000");     // 10s without data ends the playback
+    if url.starts_with("rtsp") {
+        options.set("rtsp_transport", "tcp"); // No lost packets, no smeared frames
+    }
+    options
+}
 
-    let video_stream = ictx.stream(video_stream_index).unwrap();
+/// Open a capture device or a lavfi source with its libavdevice input format
+/// Reads of capture devices do not block, `latest_packet` polls them
+/// # Parameters
+/// - `name`: Name of the input format (`v4l2`, `lavfi`)
+/// - `path`: Path to the device, or filtergraph of the source
+/// - `capture`: Is it a capture device?
+#[cfg(feature = "video")]
+fn open_device(name: &str, path: &str, capture: bool) -> Result<format::context::Input, String> {
+    let device_format = ffmpeg::device::input::video()
+        .find(|f| f.name().split(',').any(|n| n == name))
+        .ok_or_else(|| format!("FFmpeg has no {} input device", name))?;
+    let mut options = ffmpeg::Dictionary::new();
+    if capture {
+        options.set("fflags", "nobuffer+nonblock");
+    }
+    format::open_with(&path, &device_format, options)
+        .map(|context| context.input())
+        .map_err(|e| format!("Failed to open device: {}", e))
+}
 
-    // Get video metadata
-    let frame_rate = video_stream.avg_frame_rate();
-    let spf = frame_rate.1 as f64 / frame_rate.0 as f64; // seconds per frame
-    let dpf = Duration::from_secs_f64(spf);
+/// Open the best video stream of an input
+/// # Parameters
+/// - `file`: Path or URL of the video, or libavdevice input as `format:path`
+/// - `filter`: libavfilter graph applied to the decoded frames
+/// - `track`: Index among the video streams, None picks the best one
+#[cfg(feature = "video")]
+fn open_video(file: &str, filter: Option<&str>, track: Option<usize>) -> Result<VideoInput, String> {
+    // Initialize FFmpeg and suppress log output
+    ffmpeg::init().map_err(|e| format!("Failed to initialize FFmpeg: {}", e))?;
+    ffmpeg::util::log::set_level(ffmpeg::util::log::Level::Quiet);
 
-    // Get total frames (if available)
-    let total_frames = video_stream.frames() as f64;
+    let ictx = if let Some((name, path)) = source::device_input(file) {
+        open_device(name, path, source::is_capture_device(file))?
+    } else if source::is_live_url(file) {
+        format::input_with_dictionary(&file, live_options(file))
+            .map_err(|e| format!("Failed to open video file: {}", e))?
+    } else {
+        format::input(&file)
+            .map_err(|e| format!("Failed to open video file: {}", e))?
+    };
+    let stream = match track {
+        Some(track) => ictx.streams()
+            .filter(|stream| stream.parameters().medium() == media::Type::Video)
+            .nth(track)
+            .ok_or_else(|| format!("No video track {}", track))?,
+        None => ictx.streams()
+            .best(media::Type::Video)
+            .ok_or("No video stream found")?,
+    };
+    let index = stream.index();
 
-    /*** SETUP DECODER ***/
-    let context_decoder = codec::context::Context::from_parameters(video_stream.parameters())
-        .expect("Failed to create codec context");
-    let mut decoder = context_decoder.decoder().video()
-        .expect("Failed to create video decoder");
+    let context_decoder = codec::context::Context::from_parameters(stream.parameters())
+        .map_err(|e| format!("Failed to create codec context: {}", e))?;
+    let decoder = context_decoder.decoder().video()
+        .map_err(|e| format!("Failed to create video decoder: {}", e))?;
+
+    // Filter the frames before scaling, the graph outputs RGBA at its own size
+    let stream_tb = stream.time_base();
+    let (graph, format, output) = match filter {
+        Some(spec) => {
+            let (graph, output) = filter_graph(&decoder, stream_tb, spec)?;
+            (Some(graph), ffmpeg::format::Pixel::RGBA, output)
+        }
+        None => {
+            let output = GraphOutput {
+                width: decoder.width(),
+                height: decoder.height(),
+                time_base: stream_tb,
+                frame_rate: ffmpeg::Rational(0, 1),
+            };
+            (None, decoder.format(), output)
+        }
+    };
 
     // Setup scaler to convert to RGBA
-    let mut scaler = scaling::Context::get(
-        decoder.format(),
-        decoder.width(),
-        decoder.height(),
+    let scaler = scaling::Context::get(
+        format,
+        output.width,
+        output.height,
         ffmpeg::format::Pixel::RGBA,
-        decoder.width(),
-        decoder.height(),
+        output.width,
+        output.height,
         scaling::Flags::BILINEAR,
-    ).expect("Failed to create scaler");
+    ).map_err(|e| format!("Failed to create scaler: {}", e))?;
 
-    /*** AUDIO ***/
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let sink = Sink::try_new(&stream_handle).unwrap();
-    let audio_source = if audio {
-        extract_audio(file).ok()
-    } else {
-        None
+    let decoder = FrameDecoder {
+        decoder,
+        filter: graph,
+        spec: filter.map(String::from),
+        stream_tb,
+        output,
     };
+    Ok(VideoInput { ictx, index, decoder, scaler })
+}
 
-    /*** PRE-CALCULATE DIMENSIONS ***/
-    // Get video dimensions from first frame decode to calculate target size once
-    let video_w = decoder.width() as f32;
-    let video_h = decoder.height() as f32;
-    let aspect_ratio = video_w / video_h;
-
-    // Calculate target dimensions based on resolution mode
-    let (target_h, target_w) = if res {
-        // In high-res mode, we use half-pixel characters (2 pixels per char vertically)
-        let max_h = height * 2;
-        let calc_w = (max_h as f32 * aspect_ratio) as u32;
-
-        if calc_w <= width {
-            (max_h, calc_w)
-        } else {
-            // Width constraint is tighter
-            let fit_h = (width as f32 / aspect_ratio) as u32;
-            (fit_h, width)
+/// Read the newest video packet of a capture device
+/// Intra-only codecs drop the packets queued behind it, others need all of them to decode
+/// # Parameters
+/// - `ictx`: Device opened without blocking reads
+/// - `index`: Index of the video stream
+/// - `drop`: Drop the queued packets
+#[cfg(feature = "video")]
+fn latest_packet(ictx: &mut format::context::Input, index: usize,
+                 drop: bool) -> Option<ffmpeg::Packet> {
+    let mut latest = None;
+    loop {
+        let mut packet = ffmpeg::Packet::empty();
+        match packet.read(ictx) {
+            Ok(()) if packet.stream() == index => {
+                if !drop {
+                    return Some(packet);
+                }
+                latest = Some(packet);
+            }
+            Ok(()) => (),
+            // Nothing queued: show what we have or wait for the next frame
+            Err(ffmpeg::Error::Other { errno: libc::EAGAIN }) => match latest {
+                Some(packet) => return Some(packet),
+                None => sleep(Duration::from_millis(1)),
+            },
+            Err(_) => return latest,
         }
+    }
+}
+
+/// Size of the rendered frames: ((w, h) fitted to the canvas, (w, h) shown after cropping)
+/// # Parameters
+/// - `w`, `h`: Size of the video in pixels
+/// - `width`: Width of the canvas in characters
+/// - `height`: Height of the canvas in characters
+/// - `res`: Are we using the half pixel mode?
+/// - `fit`: How the video fills the canvas
+pub fn frame_layout(w: u32, h: u32, width: u32, height: u32, res: bool,
+                    fit: Fit) -> ((u32, u32), (u32, u32)) {
+    let (target_w, target_h) = fit_dimensions(w, h, width, height, res, fit);
+    let max_h = if res { height * 2 } else { height };
+    ((target_w, target_h), (target_w.min(width), target_h.min(max_h)))
+}
+
+/// Resize, crop and color a decoded frame, then convert it to cells
+/// # Parameters
+/// - `frame`: RGBA frame
+/// - `layout`: Fitted and cropped sizes from `frame_layout`
+/// - `res`: Are we using the half pixel mode?
+/// - `bg`: Background for semi-transparent pixels
+/// - `adjust`: Color adjustments
+pub fn frame_cells(frame: &RgbaImage, layout: ((u32, u32), (u32, u32)), res: bool,
+                   bg: Background, adjust: &Adjustments) -> Vec<Vec<Cell>> {
+    let ((target_w, target_h), (crop_w, crop_h)) = layout;
+    let mut resized_img = resize_image(frame, target_w, target_h);
+    if (crop_w, crop_h) != (target_w, target_h) { // Crop the overflow
+        resized_img = crop_imm(&resized_img,
+                               (target_w - crop_w) / 2,
+                               (target_h - crop_h) / 2,
+                               crop_w, crop_h).to_image();
+    }
+    adjust.apply(&mut resized_img);
+    blend_alpha(&mut resized_img, bg);
+    if res {
+        image_cells_hpm(&resized_img)
     } else {
-        // Normal mode: 1 pixel per character vertically
-        let calc_w = (height as f32 * aspect_ratio * 2.0) as u32; // *2 for char width/height ratio
+        image_cells(&resized_img)
+    }
+}
 
-        if calc_w <= width {
-            (height, calc_w)
-        } else {
-            // Width constraint is tighter
-            let fit_h = (width as f32 / (aspect_ratio * 2.0)) as u32;
-            (fit_h, width)
+/// Render frames of a video to `.ans` files, without syncing nor moving the cursor
+/// # Parameters
+/// - `file`: Path to the video file
+/// - `filter`: libavfilter graph applied before scaling
+/// - `render`: Canvas size, fit and colors
+/// - `dir`: Directory receiving the files
+/// - `frames`: Indices of the rendered frames, from 0, end excluded
+#[cfg(feature = "video")]
+pub fn dump_frames(file: &str, filter: Option<&str>, render: &RenderOptions, dir: &str,
+                   frames: Range<u64>) -> Result<(), String> {
+    let &RenderOptions { width, height, res, fit, bg, ref adjust } = render;
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    let VideoInput { mut ictx, index, mut decoder, mut scaler } = open_video(file, filter, None)?;
+    let layout = frame_layout(decoder.width(), decoder.height(), width, height, res, fit);
+
+    let mut frame_num: u64 = 0;
+    let mut decoded = VideoFrame::empty();
+    let mut render = |decoded: &VideoFrame, frame_num: u64| -> Result<(), String> {
+        if frames.contains(&frame_num) {
+            let rgba_img = frame_to_rgba(decoded, &mut scaler)?;
+            let cells = frame_cells(&rgba_img, layout, res, bg, adjust);
+            let path = Path::new(dir).join(format!("frame_{:06}.ans", frame_num));
+            export::export(&path.to_string_lossy(), &cells)?;
+        }
+        Ok(())
+    };
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != index {
+            continue;
+        }
+        decoder.send_packet(&packet).ok();
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            render(&decoded, frame_num)?;
+            frame_num += 1;
+        }
+        if frame_num >= frames.end {
+            return Ok(());
+        }
+    }
+
+    // Frames left in the decoder
+    decoder.send_eof().ok();
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        render(&decoded, frame_num)?;
+        frame_num += 1;
+    }
+    Ok(())
+}
+
+/// Builds without the `video` feature cannot decode videos
+#[cfg(not(feature = "video"))]
+pub fn dump_frames(_file: &str, _filter: Option<&str>, _render: &RenderOptions, _dir: &str,
+                   _frames: Range<u64>) -> Result<(), String> {
+    Err(NO_VIDEO.to_string())
+}
+
+/// Seek an input to the keyframe before a position, inputs that cannot seek stay where they are
+/// # Parameters
+/// - `ictx`: Opened input
+/// - `position`: Position in seconds
+#[cfg(feature = "video")]
+fn seek_keyframe(ictx: &mut format::context::Input, position: f64) {
+    let ts = (position * 1_000_000.0) as i64; // AV_TIME_BASE units
+    ictx.seek(ts, ..ts).ok();
+}
+
+/// Decode the frame shown at a given time: the last one starting at or before it
+/// # Parameters
+/// - `file`: Path to the video file
+/// - `filter`: libavfilter graph applied before scaling
+/// - `time`: Position in seconds
+#[cfg(feature = "video")]
+pub fn frame_at(file: &str, filter: Option<&str>, time: f64) -> Result<RgbaImage, String> {
+    let VideoInput { mut ictx, index, mut decoder, mut scaler } = open_video(file, filter, None)?;
+    let stream = ictx.stream(index).unwrap();
+    let time_base = stream.time_base();
+    let tb = time_base.0 as f64 / time_base.1 as f64;
+    let video_start = stream.start_time().max(0) as f64 * tb; // Positions count from the first frame
+
+    // Jump to the keyframe before the position, streams that cannot seek are decoded from the start
+    seek_keyframe(&mut ictx, video_start + time);
+
+    let mut decoded = VideoFrame::empty();
+    let mut last = None;
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != index {
+            continue;
+        }
+        decoder.send_packet(&packet).ok();
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            // The frame on screen is the last one starting at or before the position
+            let position = decoded.timestamp().map(|pts| pts as f64 * decoder.time_base() - video_start);
+            if position.is_some_and(|position| position > time) {
+                if let Some(previous) = last {
+                    return Ok(previous);
+                }
+            }
+            last = Some(frame_to_rgba(&decoded, &mut scaler)?);
+            if position.is_none_or(|position| position >= time) {
+                return Ok(last.unwrap());
+            }
+        }
+    }
+
+    // Past the end: show the last frame
+    decoder.send_eof().ok();
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        last = Some(frame_to_rgba(&decoded, &mut scaler)?);
+    }
+    last.ok_or_else(|| "No frame decoded".to_string())
+}
+
+/// Builds without the `video` feature cannot decode videos
+#[cfg(not(feature = "video"))]
+pub fn frame_at(_file: &str, _filter: Option<&str>, _time: f64) -> Result<RgbaImage, String> {
+    Err(NO_VIDEO.to_string())
+}
+
+/// Process and print the frame of a video shown at a given time
+/// # Parameters:
+/// - `file`: Path to the video file
+/// - `filter`: libavfilter graph applied before scaling
+/// - `time`: Position in seconds
+/// - `render`: Canvas size, fit and colors
+/// - `output`: File receiving the render instead of stdout
+pub fn process_frame_at(file: &str, filter: Option<&str>, time: f64, render: &RenderOptions,
+                        output: Option<&str>) {
+    match frame_at(file, filter, time) {
+        Ok(img) => display_image(img, render, output),
+        Err(e) => {
+            eprintln!("{}: {}", file, e);
+            source::exit(4);
+        }
+    }
+}
+
+/// Playback options of a video
+#[derive(Debug, Clone, Default)]
+pub struct VideoOptions {
+    pub audio: bool,                      // Play the audio track
+    pub loop_video: bool,                 // Start again at the end
+    pub sync: bool,                       // Skip frames to keep up with realtime
+    pub debug: bool,                      // Print debug stats under the video
+    pub record: Option<String>,           // asciicast file receiving the escape stream
+    pub live: bool,                       // Network stream: no end detection nor seeking
+    pub latest: bool,                     // Capture device: render the newest frame, drop the queue
+    pub filter: Option<String>,           // libavfilter graph applied before scaling
+    pub subtitles: Option<SubtitleTrack>, // Text subtitles shown under the video
+    pub video_track: Option<usize>,       // Index among the video streams, default: best
+    pub audio_track: Option<usize>,       // Index among the audio streams, default: FFmpeg's pick
+    pub start: Option<f64>,               // Play from this position, in seconds
+    pub end: Option<f64>,                 // Stop, or loop back to `start`, at this position
+    pub speed: Option<f64>,               // Playback rate, default: realtime
+    pub step: bool,                       // Advance one frame per key press
+}
+
+/// Print a video using native FFmpeg decoder (no disk I/O, no subprocess spawning)
+/// # Parameters
+/// - `file`: Path to the video file
+/// - `render`: Terminal size, fit and colors
+/// - `opts`: Playback options
+#[cfg(feature = "video")]
+pub fn process_video(file: &str, render: &RenderOptions, opts: &VideoOptions) {
+    let &RenderOptions { width, height, res, fit, bg, ref adjust } = render;
+    let (loop_video, debug) = (opts.loop_video, opts.debug);
+    let sync = opts.sync && !opts.step; // Stepping waits for the keys, not the clock
+
+    // Step mode reads single key presses
+    if opts.step {
+        if let Err(e) = term::enter_raw_mode() {
+            eprintln!("<--step> {}", e);
+            source::exit(7);
+        }
+    }
+
+    // Clear screen and hide cursor FIRST (before any processing)
+    let clear = "\x1b[2J\x1b[H\x1b[?25l"; // Clear screen, move home, hide cursor
+    print!("{}", clear);
+    stdout().flush().unwrap();
+
+    /*** OPEN VIDEO FILE ***/
+    let VideoInput { mut ictx, index: video_stream_index, mut decoder, mut scaler } =
+        match open_video(file, opts.filter.as_deref(), opts.video_track) {
+            Ok(input) => input,
+            Err(e) => {
+                term::restore();
+                eprintln!("{}: {}", file, e);
+                source::exit(4);
+            }
+        };
+    let video_stream = ictx.stream(video_stream_index).unwrap();
+
+    // Get video metadata, the filter graph may change the rate and live streams often do not know their average one
+    let frame_rate = [decoder.frame_rate(), video_stream.avg_frame_rate(), video_stream.rate()].iter().copied()
+        .find(|rate| rate.0 > 0 && rate.1 > 0)
+        .unwrap_or(ffmpeg::Rational(25, 1));
+    let spf = frame_rate.1 as f64 / frame_rate.0 as f64; // seconds per frame, in the media
+    let pspf = spf / opts.speed.unwrap_or(1.0);           // seconds per frame, on screen
+    let dpf = Duration::from_secs_f64(pspf);
+
+    // Get total frames (if available), live streams and filtered frames play until they stop
+    let total_frames = if opts.live || opts.filter.is_some() { 0.0 } else { video_stream.frames() as f64 };
+
+    /*** SUBTITLES ***/
+    // Cues of a file are loaded now, those of the video are decoded along its packets
+    let time_base = video_stream.time_base();
+    let video_tb = time_base.0 as f64 / time_base.1 as f64;
+    let video_start = video_stream.start_time().max(0) as f64 * video_tb;
+    let frame_tb = decoder.time_base(); // Timestamps of the frames out of the filter graph
+    let subtitles = match &opts.subtitles {
+        None => Ok((None, None)),
+        Some(SubtitleTrack { file: Some(path), index }) => subtitle::load(path, *index)
+            .map(|subtitles| (Some(subtitles), None))
+            .map_err(|e| format!("{}: {}", path, e)),
+        Some(SubtitleTrack { file: None, index }) => SubtitleDecoder::open(&ictx, *index, video_start)
+            .map(|decoder| (Some(Subtitles::default()), Some(decoder)))
+            .map_err(|e| format!("{}: {}", file, e)),
+    };
+    let (mut subtitles, mut sub_decoder) = match subtitles {
+        Ok(subtitles) => subtitles,
+        Err(e) => {
+            term::restore();
+            eprintln!("{}", e);
+            source::exit(4);
         }
     };
+    let sub_lines = if subtitles.is_some() { SUBTITLE_LINES } else { 0 };
+
+    /*** SEGMENT ***/
+    // Jump to the keyframe before the start, frames up to it are decoded and discarded
+    let segment_start = opts.start.unwrap_or(0.0);
+    if opts.start.is_some() {
+        seek_keyframe(&mut ictx, video_start + segment_start);
+    }
+
+    /*** AUDIO ***/
+    // Only open the output device when needed, headless machines have none
+    let audio = if opts.audio { AudioPlayer::open(file, opts) } else { None };
+
+    /*** PRE-CALCULATE DIMENSIONS ***/
+    // Calculate target dimensions based on resolution mode, subtitles take the last lines
+    let video_height = height.saturating_sub(sub_lines).max(1);
+    let layout = frame_layout(decoder.width(), decoder.height(), width, video_height, res, fit);
+    let (crop_w, crop_h) = layout.1;
 
     // Calculate actual display height (in terminal lines)
     let display_height = if res {
-        (target_h + 1) / 2  // Half-pixel mode uses 2 pixels per line
+        crop_h.div_ceil(2)  // Half-pixel mode uses 2 pixels per line
     } else {
-        target_h
+        crop_h
+    };
+
+    /*** RECORDING ***/
+    let mut recorder = match &opts.record {
+        Some(path) => match Recorder::create(path, crop_w, display_height + sub_lines + 1) {
+            Ok(mut recorder) => {
+                recorder.output(0.0, clear.as_bytes()).ok();
+                Some(recorder)
+            }
+            Err(e) => {
+                term::restore();
+                eprintln!("{}", e);
+                clean_tmp_files(); // The audio track may be extracted already
+                source::exit(10);
+            }
+        },
+        None => None,
     };
+    let mut loop_offset = 0.0; // Duration of the previous loops, in seconds
+
+    // Frames of intra-only codecs decode alone, queued packets can be dropped
+    let drop_queued = matches!(decoder.id(), codec::Id::RAWVIDEO | codec::Id::MJPEG);
 
     /*** PROCESSING ***/
     let mut frame_num: u64 = 0;
@@ -338,8 +1217,26 @@ pub fn process_video(file: &str, width: u32, height: u32, audio: bool,
     'main_loop: loop {
         let mut reached_end = false;
 
-        for (stream, packet) in ictx.packets() {
-            if stream.index() != video_stream_index {
+        // Capture devices skip to their newest packet instead of playing the queue
+        let packets: Box<dyn Iterator<Item = (usize, ffmpeg::Packet)>> = if opts.latest {
+            Box::new(std::iter::from_fn(|| {
+                latest_packet(&mut ictx, video_stream_index, drop_queued)
+                    .map(|packet| (video_stream_index, packet))
+            }))
+        } else {
+            Box::new(ictx.packets().map(|(stream, packet)| (stream.index(), packet)))
+        };
+
+        for (stream_index, packet) in packets {
+            if let Some(sub_decoder) = sub_decoder.as_mut() {
+                if stream_index == sub_decoder.index {
+                    if let (Some(cue), Some(subtitles)) = (sub_decoder.decode(&packet), subtitles.as_mut()) {
+                        subtitles.push(cue);
+                    }
+                    continue;
+                }
+            }
+            if stream_index != video_stream_index {
                 continue;
             }
 
@@ -348,19 +1245,24 @@ pub fn process_video(file: &str, width: u32, height: u32, audio: bool,
 
             let mut decoded = VideoFrame::empty();
             while decoder.receive_frame(&mut decoded).is_ok() {
+                // Position of the frame in the media, from its timestamp
+                let pts = decoded.timestamp().map(|pts| pts as f64 * frame_tb - video_start);
+                let position = pts.unwrap_or(segment_start + frame_num as f64 * spf);
+                if pts.is_some() && position < segment_start - spf / 2.0 {
+                    continue; // Before the start, after a seek to the previous keyframe
+                }
+                if opts.end.is_some_and(|end| position >= end) {
+                    reached_end = true;
+                    break;
+                }
+
                 // Start timing and audio on first frame
                 if start_time.is_none() {
                     start_time = Some(Instant::now());
 
                     // Start audio playback synchronized with first frame
-                    if audio_source.is_some() && !audio_started {
-                        // Clone the source for playback
-                        if let Ok(audio_file) = File::open(format!("/tmp/batimg_audio_{}.mp3", std::process::id())) {
-                            if let Ok(decoder) = Decoder::new(BufReader::new(audio_file)) {
-                                sink.append(decoder.repeat_infinite());
-                                audio_started = true;
-                            }
-                        }
+                    if let Some(audio) = audio.as_ref().filter(|_| !audio_started) {
+                        audio_started = audio.play();
                     }
                 }
 
@@ -371,7 +1273,7 @@ pub fn process_video(file: &str, width: u32, height: u32, audio: bool,
                         if let Some(st) = start_time {
                             let now = Instant::now();
                             let elapsed = now.duration_since(st);
-                            let expected_frame = (elapsed.as_secs_f64() / spf) as u64;
+                            let expected_frame = (elapsed.as_secs_f64() / pspf) as u64;
 
                             // If we're behind by more than 1 frame, skip rendering
                             if frame_num < expected_frame.saturating_sub(1) {
@@ -395,33 +1297,45 @@ pub fn process_video(file: &str, width: u32, height: u32, audio: bool,
                     };
 
                     if should_render {
-                        // Resize image with pre-calculated dimensions
-                        let resized_img = resize_image(&rgba_img, target_w, target_h);
+                        // Render the frame with pre-calculated dimensions
+                        let cells = frame_cells(&rgba_img, layout, res, bg, adjust);
+                        let mut buffer = Vec::new();
+                        write_ansi(&mut buffer, &cells).ok();
+                        let time = loop_offset + frame_num as f64 * pspf;
 
-                        // Print the frame
-                        if res {
-                            print_image_hpm(resized_img);
-                        } else {
-                            print_image(resized_img);
+                        // Active cues, timed by the timestamp of the frame
+                        if let Some(subtitles) = &subtitles {
+                            for line in subtitle::layout(&subtitles.active(position),
+                                                         crop_w as usize, sub_lines as usize) {
+                                writeln!(buffer, "\x1b[2K{}", line).ok(); // Clear the previous cue
+                            }
                         }
-                    }
-
-                    if should_render {
                         frame_num += 1;
 
                         // Debug info
                         if debug {
                             if let Some(st) = start_time {
                                 let elapsed = st.elapsed().as_secs_f64();
-                                let expected_time = frame_num as f64 * spf;
+                                let expected_time = frame_num as f64 * pspf;
                                 let drift = elapsed - expected_time;
-                                print!("Frame: {} | Drift: {:.3}s | FPS: {:.1}",
-                                       frame_num, drift, frame_num as f64 / elapsed);
+                                write!(buffer, "Frame: {} | Drift: {:.3}s | FPS: {:.1}",
+                                       frame_num, drift, frame_num as f64 / elapsed).ok();
                             }
                         }
+                        write!(buffer, "\x1b[{}F", display_height + sub_lines).ok(); // Move cursor to beginning
 
-                        stdout().flush().unwrap();
-                        print!("\x1b[{}F", display_height); // Move cursor to beginning
+                        // Print the frame, and record it at its presentation time
+                        let mut out = stdout().lock();
+                        out.write_all(&buffer).ok();
+                        out.flush().ok();
+                        if let Some(recorder) = recorder.as_mut() {
+                            recorder.output(time, &buffer).ok();
+                        }
+
+                        // Wait for the next key press before decoding on
+                        if opts.step && matches!(term::read_key(), term::Key::Char('q') | term::Key::Escape) {
+                            break 'main_loop;
+                        }
                     }
                 }
 
@@ -441,8 +1355,9 @@ pub fn process_video(file: &str, width: u32, height: u32, audio: bool,
         if reached_end || !loop_video {
             if loop_video && reached_end {
                 // Seek back to beginning for loop
-                ictx.seek(0, ..0).ok();
+                seek_keyframe(&mut ictx, video_start + segment_start);
                 decoder.flush();
+                loop_offset += frame_num as f64 * pspf;
                 frame_num = 0;
                 start_time = None;
                 audio_started = false;
@@ -453,8 +1368,9 @@ pub fn process_video(file: &str, width: u32, height: u32, audio: bool,
 
         // If packets ended naturally and we're looping
         if loop_video {
-            ictx.seek(0, ..0).ok();
+            seek_keyframe(&mut ictx, video_start + segment_start);
             decoder.flush();
+            loop_offset += frame_num as f64 * pspf;
             frame_num = 0;
             start_time = None;
             audio_started = false;
@@ -464,12 +1380,18 @@ pub fn process_video(file: &str, width: u32, height: u32, audio: bool,
     }
 
     // Clean up and restore terminal state
-    print!("\x1b[2J");     // Clear entire screen
-    print!("\x1b[H");      // Move cursor to home position
-    print!("\x1b[?25h");   // Show cursor again
-    print!("\x1b[0m");     // Reset all text attributes
-    stdout().flush().unwrap();
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.finish() {
+            eprintln!("{}: {}", opts.record.as_deref().unwrap_or_default(), e);
+        }
+    }
+    term::restore();
     clean_tmp_files();
 }
 
-
+/// Builds without the `video` feature cannot play videos
+#[cfg(not(feature = "video"))]
+pub fn process_video(file: &str, _render: &RenderOptions, _opts: &VideoOptions) {
+    eprintln!("{}: {}", file, NO_VIDEO);
+    source::exit(4);
+}
@@ -0,0 +1,13 @@
+// Image and video rendering for terminals, used by the batimg cli
+// Author: Sofiane Djerbi (@sofianedjerbi)
+pub mod adjust;
+pub mod animation;
+pub mod color;
+pub mod export;
+pub mod graphics;
+pub mod probe;
+pub mod record;
+pub mod source;
+pub mod subtitle;
+pub mod term;
+pub mod viewer;
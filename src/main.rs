@@ -3,134 +3,146 @@
 // Author: Sofiane Djerbi (@sofianedjerbi)
 use terminal_size::{Width, Height, terminal_size};
 use clap::{App, Arg};
-use ctrlc;
-use regex::Regex;
 
+use std::ops::Range;
 use std::path::Path;
-use std::process::{Command, Stdio};
-use std::io::{BufRead, BufReader};
-use std::thread;
-use std::time::Duration;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
-
-mod graphics;
-
-
-const SUPPORTED_VIDEOS: [&str; 23] = ["gif", "avi", "mp4", "mkv", "m2v",
-                                      "ogg", "ogv", "aac", "aax",
-                                      "mov", "wmv", "avchd", "m4p",
-                                      "f4v", "swf", "mkv", "yuv", "webm",
-                                      "amv", "m4v", "3gp", "3g2", "nsv"];
-
-fn is_youtube_url(input: &str) -> bool {
-    let youtube_regex = Regex::new(
-        r"^(https?://)?(www\.)?(youtube\.com/(watch\?v=|shorts/)|youtu\.be/)[\w-]+"
-    ).unwrap();
-    youtube_regex.is_match(input)
-}
 
-fn download_youtube_video(url: &str) -> Result<String, String> {
-    println!("Fetching YouTube video...");
-
-    // Create a temporary directory for the video
-    let temp_dir = tempfile::tempdir()
-        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
-    let temp_path = temp_dir.path().join("video.mp4");
-
-    // Start loading animation in a separate thread
-    let loading = Arc::new(AtomicBool::new(true));
-    let loading_clone = Arc::clone(&loading);
-
-    let spinner_thread = thread::spawn(move || {
-        let frames = vec!["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-        let mut idx = 0;
-        while loading_clone.load(Ordering::Relaxed) {
-            print!("\r{} Downloading... ", frames[idx]);
-            std::io::Write::flush(&mut std::io::stdout()).ok();
-            idx = (idx + 1) % frames.len();
-            thread::sleep(Duration::from_millis(80));
-        }
-        print!("\r\x1b[K"); // Clear the line
-        std::io::Write::flush(&mut std::io::stdout()).ok();
-    });
+use batimg::{adjust, animation, export, graphics, probe, term, viewer};
+use batimg::source::{self, Source, Media, DEFAULT_YTDL_FORMAT};
+use batimg::subtitle::SubtitleTrack;
 
-    // Use yt-dlp to download the video with progress output
-    let child_result = Command::new("yt-dlp")
-        .arg("-f")
-        .arg("best[height<=720][ext=mp4]/best[height<=720]/best")
-        .arg("--no-playlist")
-        .arg("--quiet")
-        .arg("--progress")
-        .arg("--newline")
-        .arg("-o")
-        .arg(&temp_path)
-        .arg(url)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn();
-
-    let mut child = match child_result {
-        Ok(c) => c,
-        Err(e) => {
-            loading.store(false, Ordering::Relaxed);
-            spinner_thread.join().ok();
-            return Err(format!("Failed to execute yt-dlp: {}. Make sure yt-dlp is installed.", e));
-        }
-    };
 
-    // Read stderr in real-time for progress
-    if let Some(stderr) = child.stderr.take() {
-        let reader = BufReader::new(stderr);
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                // Show download progress percentage
-                if line.contains("%") && line.contains("of") {
-                    print!("\r\x1b[K{}", line.trim());
-                    std::io::Write::flush(&mut std::io::stdout()).ok();
+// Width of a thumbnail (in characters) for the automatic grid layout
+const GRID_CELL_WIDTH: u32 = 24;
+
+/// Expand glob patterns that the shell left untouched (e.g. quoted)
+fn expand_globs<'a>(inputs: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let is_pattern = input.contains(['*', '?', '[']);
+        if is_pattern && !Path::new(input).exists() {
+            if let Ok(paths) = glob::glob(input) {
+                let matched: Vec<String> = paths.flatten()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .collect();
+                if !matched.is_empty() {
+                    files.extend(matched);
+                    continue;
                 }
             }
         }
+        files.push(input.to_string());
     }
+    files
+}
+
+/// Parse a grid layout `NxM` (columns x rows) or `auto`
+/// Returns the number of columns and the height of a cell in characters
+fn parse_grid(grid: &str, width: u32, height: u32) -> Result<(u32, u32), String> {
+    if grid == "auto" {
+        let cols = (width / GRID_CELL_WIDTH).max(1);
+        let thumb_h = (width / cols) as f64 / term::cell_ratio(); // Square thumbnails
+        return Ok((cols, thumb_h as u32 + 1));
+    }
+    let (cols, rows) = grid.split_once('x')
+        .and_then(|(n, m)| Some((n.parse::<u32>().ok()?, m.parse::<u32>().ok()?)))
+        .filter(|&(n, m)| n > 0 && m > 0)
+        .ok_or_else(|| "should look like NxM, e.g. 4x3.".to_string())?;
+    Ok((cols, height / rows))
+}
 
-    let status = match child.wait() {
-        Ok(s) => s,
-        Err(e) => {
-            loading.store(false, Ordering::Relaxed);
-            spinner_thread.join().ok();
-            return Err(format!("Failed to wait for yt-dlp: {}", e));
+/// Parse a `#rrggbb` color
+fn parse_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Parse a time `[[HH:]MM:]SS[.ms]` in seconds
+fn parse_time(time: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for (i, part) in time.split(':').enumerate() {
+        if i > 2 {
+            return None;
         }
-    };
+        let value = part.parse::<f64>().ok().filter(|v| *v >= 0.0 && v.is_finite())?;
+        seconds = seconds * 60.0 + value;
+    }
+    Some(seconds)
+}
 
-    // Stop the loading animation
-    loading.store(false, Ordering::Relaxed);
-    spinner_thread.join().ok();
+/// Parse a frame range `N..M`, `N..`, `..M` or `N` (from 0, end excluded)
+fn parse_frames(frames: &str) -> Option<Range<u64>> {
+    match frames.split_once("..") {
+        Some((first, last)) => {
+            let first = if first.is_empty() { 0 } else { first.parse().ok()? };
+            let last = if last.is_empty() { u64::MAX } else { last.parse().ok()? };
+            Some(first..last).filter(|range| !range.is_empty())
+        }
+        None => frames.parse::<u64>().ok().map(|n| n..n + 1),
+    }
+}
 
-    if !status.success() {
-        return Err(format!("yt-dlp failed with exit code: {:?}", status.code()));
+/// Parse a color adjustment option, exits if it is not valid
+fn parse_factor(matches: &clap::ArgMatches, name: &str,
+                valid: fn(f32) -> bool, expected: &str) -> f32 {
+    let value = matches.value_of(name).unwrap_or_default();
+    match value.parse::<f32>() {
+        Ok(num) if valid(num) => num,
+        _ => {
+            eprintln!("<--{}> should be {}.", name, expected);
+            std::process::exit(7);
+        }
     }
+}
 
-    if !temp_path.exists() {
-        return Err("Download failed: video file not created".to_string());
+/// Parse a canvas dimension option, exits if it is not an unsigned integer
+fn parse_dimension(matches: &clap::ArgMatches, name: &str) -> Option<u32> {
+    let value = matches.value_of(name)?;
+    match value.parse::<u32>() {
+        Ok(num)   => Some(num),
+        Err(_err) => {
+            eprintln!("<--{}> should be an unsigned integer.", name);
+            std::process::exit(7);
+        }
     }
+}
 
-    println!("\r\x1b[K✓ Download complete!");
+/// Parse a track number option, exits if it is not an unsigned integer
+fn parse_track(matches: &clap::ArgMatches, name: &str) -> Option<usize> {
+    let value = matches.value_of(name)?;
+    match value.parse::<usize>() {
+        Ok(num)   => Some(num),
+        Err(_err) => {
+            eprintln!("<--{}> should be a track number, from 0.", name);
+            std::process::exit(7);
+        }
+    }
+}
 
-    // Convert path to string and leak the temp dir to keep file alive
-    let path_str = temp_path.to_str().unwrap().to_string();
-    std::mem::forget(temp_dir); // Keep temp dir alive
-    Ok(path_str)
+/// Keep local files only, for the modes that need to reopen them
+fn local_files(inputs: Vec<String>) -> Vec<String> {
+    inputs.into_iter()
+        .filter(|input| {
+            let local = Source::parse(input).is_local();
+            if !local {
+                eprintln!("{}: Not a local image, skipped.", input);
+            }
+            local
+        })
+        .collect()
 }
 
 
 fn main() {
     // Handle CTRL + C (on videos)
     ctrlc::set_handler(move || {
-        print!("\x1b[2J");     // Clear entire screen
-        print!("\x1b[H");      // Move cursor to home position
-        print!("\x1b[?25h");   // Show cursor again
-        print!("\x1b[0m");     // Reset all text attributes
-        std::io::Write::flush(&mut std::io::stdout()).ok();
+        term::restore();       // Restore terminal state
         graphics::clean_tmp_files(); // Remove tmp files
+        source::clean_temp_files();  // Stop yt-dlp, remove downloads
         std::process::exit(0); // Exit process cleanly
     }).expect("Error setting Ctrl-C handler");
 
@@ -139,12 +151,83 @@ fn main() {
         .version("1.1")
         .author("Sofiane Djerbi <@sofianedjerbi>")
         .about("Graphic content on your tty")
-        .arg(Arg::new("size")
-            .short('s')
-            .long("size")
-            .help("Canvas size")
+        .subcommand_negates_reqs(true)
+        .subcommand(App::new("info")
+            .about("Print a summary of a media: container, streams and codecs, or image properties")
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Print the summary as JSON"))
+            .arg(Arg::new("FILE")
+                .help("Path or URL of the media")
+                .required(true)
+                .index(1)))
+        .arg(Arg::new("width")
+            .short('W')
+            .long("width")
+            .help("Canvas width in characters")
             .value_name("u32")
             .takes_value(true))
+        .arg(Arg::new("height")
+            .short('H')
+            .long("height")
+            .help("Canvas height in characters")
+            .value_name("u32")
+            .takes_value(true)
+            .short_alias('s')
+            .alias("size"))
+        .arg(Arg::new("fit")
+            .short('f')
+            .long("fit")
+            .help("How the media fills the canvas")
+            .value_name("mode")
+            .takes_value(true)
+            .possible_values(["contain", "cover", "stretch", "none"])
+            .default_value("contain"))
+        .arg(Arg::new("background")
+            .short('b')
+            .long("background")
+            .help("Background for semi-transparent pixels: #rrggbb, terminal or checkerboard")
+            .value_name("color")
+            .takes_value(true)
+            .default_value("terminal"))
+        .arg(Arg::new("brightness")
+            .long("brightness")
+            .help("Brightness offset, from -1 to 1")
+            .value_name("f32")
+            .takes_value(true)
+            .default_value("0")
+            .allow_hyphen_values(true))
+        .arg(Arg::new("contrast")
+            .long("contrast")
+            .help("Contrast factor")
+            .value_name("f32")
+            .takes_value(true)
+            .default_value("1"))
+        .arg(Arg::new("saturation")
+            .long("saturation")
+            .help("Saturation factor (0 for grayscale)")
+            .value_name("f32")
+            .takes_value(true)
+            .default_value("1"))
+        .arg(Arg::new("gamma")
+            .long("gamma")
+            .help("Gamma correction")
+            .value_name("f32")
+            .takes_value(true)
+            .default_value("1"))
+        .arg(Arg::new("invert")
+            .long("invert")
+            .help("Invert colors")
+            .takes_value(false))
+        .arg(Arg::new("grayscale")
+            .long("grayscale")
+            .help("Drop colors")
+            .takes_value(false))
+        .arg(Arg::new("cell-ratio")
+            .long("cell-ratio")
+            .help("Height/width ratio of a character cell [default: from terminal, or 2]")
+            .value_name("f64")
+            .takes_value(true))
         .arg(Arg::new("timesync")
             .short('t')
             .long("timesync")
@@ -163,97 +246,496 @@ fn main() {
         .arg(Arg::new("loop")
             .short('l')
             .long("loop")
-            .help("Loop the video")
+            .help("Loop the video, animations loop forever")
             .takes_value(false))
         .arg(Arg::new("resolution")
             .short('r')
             .long("resolution")
             .help("Disable high resolution mode (half pixel character)")
             .takes_value(false))
+        .arg(Arg::new("grid")
+            .short('g')
+            .long("grid")
+            .help("Print images as a contact sheet (NxM columns x rows)")
+            .value_name("NxM")
+            .takes_value(true)
+            .min_values(0)
+            .require_equals(true)
+            .default_missing_value("auto"))
+        .arg(Arg::new("view")
+            .long("view")
+            .help("Browse images interactively (arrows, zoom and pan)")
+            .takes_value(false)
+            .conflicts_with("grid"))
+        .arg(Arg::new("output")
+            .short('o')
+            .long("output")
+            .help("Write the render to a file (.ans, .html or .svg) instead of the terminal")
+            .value_name("FILE")
+            .takes_value(true)
+            .conflicts_with("view"))
+        .arg(Arg::new("record")
+            .long("record")
+            .help("Record the video playback to an asciicast v2 file")
+            .value_name("FILE")
+            .takes_value(true))
+        .arg(Arg::new("dump-frames")
+            .long("dump-frames")
+            .help("Render the video frames to .ans files in a directory")
+            .value_name("DIR")
+            .takes_value(true)
+            .conflicts_with_all(&["record", "frame-at"]))
+        .arg(Arg::new("frames")
+            .long("frames")
+            .help("Frames rendered by <--dump-frames>, from 0, end excluded")
+            .value_name("N..M")
+            .takes_value(true)
+            .requires("dump-frames"))
+        .arg(Arg::new("frame-at")
+            .long("frame-at")
+            .help("Print the frame of a video shown at a time")
+            .value_name("[HH:]MM:SS")
+            .takes_value(true))
+        .arg(Arg::new("ytdl-format")
+            .long("ytdl-format")
+            .help("yt-dlp format selection for video sites")
+            .value_name("FORMAT")
+            .takes_value(true)
+            .default_value(DEFAULT_YTDL_FORMAT))
+        .arg(Arg::new("device")
+            .long("device")
+            .help("Play a V4L2 capture device live, e.g. /dev/video0 (webcams, capture cards)")
+            .value_name("PATH")
+            .takes_value(true))
+        .arg(Arg::new("subs")
+            .long("subs")
+            .help("Show the subtitles of a file (SRT, ASS, WebVTT) under the video")
+            .value_name("FILE")
+            .takes_value(true))
+        .arg(Arg::new("sub-track")
+            .long("sub-track")
+            .help("Show a subtitle track of the video, or of <--subs>, from 0")
+            .value_name("N")
+            .takes_value(true))
+        .arg(Arg::new("start")
+            .long("start")
+            .help("Play the video from a position")
+            .value_name("[HH:]MM:SS")
+            .takes_value(true)
+            .conflicts_with_all(&["dump-frames", "frame-at"]))
+        .arg(Arg::new("end")
+            .long("end")
+            .help("Stop the video at a position, or loop back to <--start> with <--loop>")
+            .value_name("[HH:]MM:SS")
+            .takes_value(true)
+            .conflicts_with_all(&["dump-frames", "frame-at"]))
+        .arg(Arg::new("duration")
+            .long("duration")
+            .help("Play the video for a duration, from <--start>")
+            .value_name("[HH:]MM:SS")
+            .takes_value(true)
+            .conflicts_with_all(&["end", "dump-frames", "frame-at"]))
+        .arg(Arg::new("speed")
+            .long("speed")
+            .help("Play the video faster or slower, from 0.25 to 4")
+            .value_name("RATE")
+            .takes_value(true)
+            .conflicts_with_all(&["dump-frames", "frame-at"]))
+        .arg(Arg::new("step")
+            .long("step")
+            .help("Advance the video one frame per key press, q to quit")
+            .takes_value(false)
+            .conflicts_with_all(&["audio", "device", "dump-frames", "frame-at"]))
+        .arg(Arg::new("video-track")
+            .long("video-track")
+            .help("Play a video track of the file, from 0 (see <--list-tracks>)")
+            .value_name("N")
+            .takes_value(true)
+            .conflicts_with_all(&["dump-frames", "frame-at"]))
+        .arg(Arg::new("audio-track")
+            .long("audio-track")
+            .help("Play an audio track of the file, from 0 (see <--list-tracks>)")
+            .value_name("N")
+            .takes_value(true)
+            .requires("audio"))
+        .arg(Arg::new("list-tracks")
+            .long("list-tracks")
+            .help("List the streams of the media (codec, resolution, language, duration) and exit"))
+        .arg(Arg::new("vf")
+            .long("vf")
+            .help("FFmpeg filter graph applied to the video before scaling, e.g. crop=iw/2:ih/2,hflip")
+            .value_name("FILTERS")
+            .takes_value(true))
         .arg(Arg::new("FILE")
-            .help("Path to the media")
+            .help("Paths to the media, URLs (direct, video sites or network streams), \
+                   lavfi:GRAPH sources or - for stdin")
             .value_name("FILE")
-            .required(true)
+            .required_unless_present("device")
             .takes_value(true)
+            .multiple_values(true)
             .index(1))
         .get_matches();
 
+    // PRINT MEDIA INFO
+    if let Some(info) = matches.subcommand_matches("info") {
+        let file = info.value_of("FILE").unwrap();
+        if Source::parse(file).is_local() && !Path::new(file).exists() {
+            eprintln!("{}: No such media.", file);
+            std::process::exit(1);
+        }
+        let summary = probe::probe(file).unwrap_or_else(|e| {
+            eprintln!("{}: {}", file, e);
+            std::process::exit(4);
+        });
+        let mut out = std::io::stdout();
+        let written = if info.is_present("json") {
+            summary.write_json(&mut out, file)
+        } else {
+            summary.write_text(&mut out, file)
+        };
+        if let Err(e) = written {
+            eprintln!("{}", e);
+            std::process::exit(10);
+        }
+        return;
+    }
+
     // Variables to populate
-    let file: String;
     let height: u32;
     let width: u32;
-    let is_video: bool;
 
     // Flag variables
     let debug: bool = matches.is_present("debug");
     let play_audio: bool = matches.is_present("audio");
     let timesync: bool = matches.is_present("timesync");
     let resolution: bool = !matches.is_present("resolution");
-    let mut loop_video: bool = matches.is_present("loop");
+    let loop_video: bool = matches.is_present("loop");
+    if play_audio && !cfg!(feature = "audio") {
+        eprintln!("<--audio> {}.", graphics::NO_AUDIO);
+        std::process::exit(7);
+    }
 
-    // GET CANVAS SIZE
-    let size = terminal_size(); // Request term size
-    if let Some(h) = matches.value_of("size") { // In options
-        height = match h.parse::<u32>() {
-            Ok(num)   => num,
-            Err(_err) => {
-                eprintln!("<--size> should be an unsigned integer.");
+    // GET CELL RATIO
+    if let Some(r) = matches.value_of("cell-ratio") { // In options
+        match r.parse::<f64>() {
+            Ok(num) if num > 0.0 => term::set_cell_ratio(num),
+            _ => {
+                eprintln!("<--cell-ratio> should be a positive number.");
                 std::process::exit(7);
             }
-        };
-        width = height * 2; // Assume 2:1 ratio for characters
-    }
-    else if let Some((Width(w), Height(h))) = size { // In terminal
-        height = (h as u32).saturating_sub(1);
-        width = w as u32;
+        }
     }
-    else { // Cannot get terminal size
-        eprintln!("Unable to get canvas size, please use <--size> option.");
-        std::process::exit(3);
+
+    // GET BACKGROUND
+    let background = match matches.value_of("background") {
+        Some("checkerboard") => graphics::Background::Checkerboard,
+        Some("terminal") | None => graphics::Background::Terminal,
+        Some(color) => match parse_color(color) {
+            Some((r, g, b)) => graphics::Background::Color(r, g, b),
+            None => {
+                eprintln!("<--background> should be #rrggbb, terminal or checkerboard.");
+                std::process::exit(7);
+            }
+        }
+    };
+
+    // GET COLOR ADJUSTMENTS
+    let adjust = adjust::Adjustments {
+        brightness: parse_factor(&matches, "brightness", |v| (-1.0..=1.0).contains(&v),
+                                 "a number from -1 to 1"),
+        contrast: parse_factor(&matches, "contrast", |v| v >= 0.0, "a positive number"),
+        saturation: parse_factor(&matches, "saturation", |v| v >= 0.0, "a positive number"),
+        gamma: parse_factor(&matches, "gamma", |v| v > 0.0, "a positive number"),
+        invert: matches.is_present("invert"),
+        grayscale: matches.is_present("grayscale"),
+    };
+
+    // GET CANVAS SIZE
+    let size = terminal_size(); // Request term size
+    let opt_width = parse_dimension(&matches, "width");
+    let opt_height = parse_dimension(&matches, "height");
+    match (opt_width, opt_height, size) {
+        (Some(w), Some(h), _) => { // In options
+            width = w;
+            height = h;
+        }
+        (w, h, Some((Width(tw), Height(th)))) => { // Completed by terminal
+            width = w.unwrap_or(tw as u32);
+            height = h.unwrap_or_else(|| (th as u32).saturating_sub(1));
+        }
+        (Some(w), None, None) => { // Square canvas
+            width = w;
+            height = (w as f64 / term::cell_ratio()) as u32;
+        }
+        (None, Some(h), None) => { // Square canvas
+            height = h;
+            width = (h as f64 * term::cell_ratio()) as u32;
+        }
+        (None, None, None) => { // Cannot get terminal size
+            eprintln!("Unable to get canvas size, please use <--width> or <--height> option.");
+            std::process::exit(3);
+        }
     }
 
-    // GET INPUT FILE OR URL
-    let input = matches.value_of("FILE").unwrap_or_else(|| {
+    let fit = match matches.value_of("fit") {
+        Some("cover")   => graphics::Fit::Cover,
+        Some("stretch") => graphics::Fit::Stretch,
+        Some("none")    => graphics::Fit::None,
+        _               => graphics::Fit::Contain,
+    };
+
+    // GET INPUT FILES OR URLS
+    let mut inputs = matches.values_of("FILE").map(expand_globs).unwrap_or_default();
+    if let Some(device) = matches.value_of("device") {
+        inputs.push(format!("v4l2:{}", device));
+    }
+    if inputs.is_empty() {
         eprintln!("No media specified.");
         std::process::exit(1);
-    });
+    }
 
-    // Check if input is a YouTube URL
-    if is_youtube_url(input) {
-        file = match download_youtube_video(input) {
-            Ok(path) => path,
-            Err(e) => {
-                eprintln!("Error downloading YouTube video: {}", e);
-                std::process::exit(2);
-            }
-        };
-    } else {
-        // Check if the file exists
-        if !Path::new(input).exists() {
+    // Check that every file exists before printing anything
+    for input in &inputs {
+        if Source::parse(input).is_local() && !Path::new(input).exists() {
             eprintln!("{}: No such media.", input);
             std::process::exit(1);
         }
-        file = input.to_string();
     }
 
-    // Check for video
-    match file.rsplit(".").next() {
-        None      => std::process::exit(11),
-        Some(ext) => {
-            is_video = SUPPORTED_VIDEOS.contains(&ext);
-            loop_video = &ext.eq("gif") ^ loop_video // If is a gif
+    // LIST TRACKS
+    if matches.is_present("list-tracks") {
+        for input in &inputs {
+            if let Err(e) = probe::print_tracks(input) {
+                eprintln!("{}: {}", input, e);
+                std::process::exit(4);
+            }
         }
+        return;
     }
 
-    // PROCESS PICTURE
-    if !is_video {
-        graphics::process_image(&file, height, resolution);
+    // GET OUTPUT FILE
+    let output = matches.value_of("output");
+    if let Some(path) = output {
+        if export::Format::from_path(path).is_none() {
+            eprintln!("<--output> should end with .ans, .html or .svg.");
+            std::process::exit(7);
+        }
+        if inputs.len() > 1 && !matches.is_present("grid") {
+            eprintln!("<--output> takes a single image, or several with <--grid>.");
+            std::process::exit(7);
+        }
     }
-    // PROCESS VIDEO
-    else {
-        graphics::process_video(&file, width, height, play_audio,
-                                resolution, loop_video,
-                                !timesync, debug);
+
+    // GET VIDEO OPTIONS
+    if matches.is_present("record") && inputs.len() > 1 {
+        eprintln!("<--record> takes a single video.");
+        std::process::exit(7);
     }
-}
+    let position = |name: &str| matches.value_of(name).map(|time| match parse_time(time) {
+        Some(seconds) => seconds,
+        None => {
+            eprintln!("<--{}> should look like [[HH:]MM:]SS, e.g. 01:23.", name);
+            std::process::exit(7);
+        }
+    });
+    let frame_at = position("frame-at");
+    let dump = matches.value_of("dump-frames").map(|dir| {
+        let frames = match matches.value_of("frames") {
+            Some(range) => parse_frames(range).unwrap_or_else(|| {
+                eprintln!("<--frames> should look like N..M, N.. or ..M, e.g. 0..100.");
+                std::process::exit(7);
+            }),
+            None => 0..u64::MAX,
+        };
+        (dir, frames)
+    });
+    let subtitles = match (matches.value_of("subs"), matches.value_of("sub-track")) {
+        (None, None) => None,
+        (file, _) => Some(SubtitleTrack {
+            file: file.map(String::from),
+            index: parse_track(&matches, "sub-track").unwrap_or(0),
+        }),
+    };
+    let start = position("start");
+    let end = position("end")
+        .or_else(|| position("duration").map(|duration| start.unwrap_or(0.0) + duration));
+    if let (Some(start), Some(end)) = (start, end) {
+        if end <= start {
+            eprintln!("<--end> should come after <--start>.");
+            std::process::exit(7);
+        }
+    }
+    let speed = matches.value_of("speed").map(|value| match value.parse::<f64>() {
+        Ok(rate) if (0.25..=4.0).contains(&rate) => rate,
+        _ => {
+            eprintln!("<--speed> should be a number from 0.25 to 4.");
+            std::process::exit(7);
+        }
+    });
+    let video_opts = graphics::VideoOptions {
+        audio: play_audio,
+        loop_video,
+        sync: !timesync,
+        debug,
+        record: matches.value_of("record").map(String::from),
+        live: false,
+        latest: false,
+        filter: matches.value_of("vf").map(String::from),
+        subtitles,
+        video_track: parse_track(&matches, "video-track"),
+        audio_track: parse_track(&matches, "audio-track"),
+        start,
+        end,
+        speed,
+        step: matches.is_present("step"),
+    };
+    let render = graphics::RenderOptions {
+        width,
+        height,
+        res: resolution,
+        fit,
+        bg: background,
+        adjust,
+    };
 
+    // PROCESS CONTACT SHEET
+    if let Some(grid) = matches.value_of("grid") {
+        let (cols, cell_height) = match parse_grid(grid, width, height) {
+            Ok(layout) => layout,
+            Err(e) => {
+                eprintln!("<--grid> {}", e);
+                std::process::exit(7);
+            }
+        };
+        let files = local_files(inputs);
+        graphics::process_grid(&files, cols, cell_height, &render, output);
+        return;
+    }
+
+    // PROCESS IMAGE VIEWER
+    if matches.is_present("view") {
+        viewer::view_images(&local_files(inputs), width, height, resolution,
+                            background, &adjust);
+        return;
+    }
+
+    let ytdl_format = matches.value_of("ytdl-format").unwrap_or(DEFAULT_YTDL_FORMAT);
+    for input in &inputs {
+        let source = Source::parse(input);
+        let media = match source.resolve(ytdl_format, video_opts.audio) {
+            Ok(media) => media,
+            Err(e) => {
+                eprintln!("{}: {}", input, e);
+                source::exit(if source == Source::Stdin { 5 } else { 2 });
+            }
+        };
+        let mut opts = video_opts.clone();
+
+        let file = match media {
+            // PROCESS PICTURE
+            Media::Image(file) => {
+                graphics::process_image(&file, &render, output);
+                continue;
+            }
+            Media::ImageData(data) => {
+                graphics::process_image_data(&data, &render, output);
+                continue;
+            }
+            Media::ImageUrl(url) => {
+                graphics::process_frame_at(&url, None, 0.0, &render, output);
+                continue;
+            }
+            // PROCESS ANIMATION
+            Media::Animation(data) => {
+                if output.is_some() && frame_at.is_none() && dump.is_none() {
+                    eprintln!("{}: <--output> only supports still images.", input);
+                    source::exit(7);
+                }
+                // Animations are decoded natively, without FFmpeg filters nor streams
+                for (option, used) in [("vf", opts.filter.is_some()),
+                                       ("subs", opts.subtitles.as_ref().is_some_and(|s| s.file.is_some())),
+                                       ("sub-track", opts.subtitles.is_some()),
+                                       ("video-track", opts.video_track.is_some())] {
+                    if used {
+                        eprintln!("{}: <--{}> is not supported on animations.", input, option);
+                        source::exit(7);
+                    }
+                }
+                if opts.audio {
+                    eprintln!("Animations have no audio, ignoring <--audio>.");
+                }
+                let frames = match animation::decode(&data) {
+                    Ok(frames) => frames,
+                    Err(e) => {
+                        eprintln!("{}: {}", input, e);
+                        source::exit(4);
+                    }
+                };
+                if opts.start.is_some_and(|start| start >= frames.duration()) {
+                    eprintln!("{}: <--start> is past the end of the animation.", input);
+                    source::exit(7);
+                }
+                if let Some(time) = frame_at {
+                    graphics::display_image(frames.frame_at(time).clone(), &render, output);
+                } else if let Some((dir, range)) = &dump {
+                    if let Err(e) = animation::dump_frames(&frames, &render, dir, range.clone()) {
+                        eprintln!("<--dump-frames> {}", e);
+                        source::exit(10);
+                    }
+                } else {
+                    animation::play_animation(&frames, &render, &opts);
+                }
+                continue;
+            }
+            Media::Video(file) => file,
+            Media::Stream(url) | Media::Live(url) => {
+                if opts.audio {
+                    eprintln!("Audio is not supported on streams, ignoring <--audio>.");
+                }
+                if opts.loop_video {
+                    eprintln!("Cannot loop a stream, ignoring <--loop>.");
+                }
+                opts.audio = false;
+                opts.loop_video = false;
+                opts.live = source::is_live_url(&url);
+                url
+            }
+            Media::Device(input) => {
+                if opts.audio {
+                    eprintln!("Audio is not supported on capture devices, ignoring <--audio>.");
+                }
+                if opts.loop_video {
+                    eprintln!("Cannot loop a capture device, ignoring <--loop>.");
+                }
+                // No sync sleep: frames are shown as soon as the device delivers them
+                opts.audio = false;
+                opts.loop_video = false;
+                opts.live = true;
+                opts.sync = false;
+                opts.latest = true;
+                input
+            }
+        };
+
+        // PROCESS VIDEO STILL
+        if let Some(time) = frame_at {
+            graphics::process_frame_at(&file, opts.filter.as_deref(), time, &render, output);
+        }
+        // PROCESS FRAME DUMP
+        else if let Some((dir, frames)) = &dump {
+            if let Err(e) = graphics::dump_frames(&file, opts.filter.as_deref(), &render, dir, frames.clone()) {
+                eprintln!("<--dump-frames> {}", e);
+                source::exit(10);
+            }
+        }
+        // PROCESS VIDEO
+        else {
+            if output.is_some() {
+                eprintln!("{}: <--output> only supports still images.", file);
+                source::exit(7);
+            }
+            graphics::process_video(&file, &render, &opts);
+        }
+    }
+    source::clean_temp_files();
+}
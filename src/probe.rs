@@ -0,0 +1,402 @@
+/// probe.rs - Describe media files: streams of videos, properties of images
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+
+use image::{AnimationDecoder, GenericImageView};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image_webp::WebPDecoder;
+
+#[cfg(feature = "video")]
+use ffmpeg_next as ffmpeg;
+#[cfg(feature = "video")]
+use ffmpeg::{codec, format, media};
+
+#[cfg(not(feature = "video"))]
+use crate::graphics::NO_VIDEO;
+use crate::animation;
+use crate::record::json_string;
+use crate::source::SNIFF_SIZE;
+
+
+/// A stream of a media file
+#[derive(Debug, Clone, PartialEq)]
+#[cfg(feature = "video")]
+pub struct StreamInfo {
+    pub index: usize,                 // Index in the container
+    pub kind: media::Type,
+    pub track: usize,                 // Index among the streams of the same kind, for <--*-track>
+    pub codec: String,
+    pub size: Option<(u32, u32)>,     // Resolution of video streams
+    pub pixel_format: Option<String>,
+    pub frame_rate: Option<f64>,      // Frames per second
+    pub frames: Option<u64>,          // Total frames, when the container tells
+    pub sample_rate: Option<u32>,     // Audio streams
+    pub channels: Option<u16>,
+    pub language: Option<String>,
+    pub duration: Option<f64>,        // Seconds
+}
+
+/// Summary of a media decoded by FFmpeg
+#[derive(Debug, Clone, PartialEq)]
+#[cfg(feature = "video")]
+pub struct VideoInfo {
+    pub container: String,
+    pub duration: Option<f64>, // Seconds
+    pub bit_rate: Option<i64>, // Bits per second
+    pub streams: Vec<StreamInfo>,
+}
+
+/// Summary of an image
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub color: String,               // Color type of the pixels, e.g. Rgba8
+    pub frames: usize,               // More than one for animations
+    pub exif: Vec<(String, String)>, // EXIF tags of the primary image
+}
+
+/// Summary of a media printed by `batimg info`
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaInfo {
+    Image(ImageInfo),
+    #[cfg(feature = "video")]
+    Video(VideoInfo),
+}
+
+/// Name of a stream type
+/// # Parameters
+/// - `kind`: Type of the stream
+#[cfg(feature = "video")]
+pub fn kind_name(kind: media::Type) -> &'static str {
+    match kind {
+        media::Type::Video => "video",
+        media::Type::Audio => "audio",
+        media::Type::Subtitle => "subtitle",
+        media::Type::Data => "data",
+        media::Type::Attachment => "attachment",
+        _ => "unknown",
+    }
+}
+
+/// Format a duration as HH:MM:SS.ss
+/// # Parameters
+/// - `seconds`: Duration in seconds
+pub fn format_duration(seconds: f64) -> String {
+    let hours = (seconds / 3600.0) as u64;
+    let minutes = (seconds % 3600.0 / 60.0) as u64;
+    format!("{:02}:{:02}:{:05.2}", hours, minutes, seconds % 60.0)
+}
+
+/// Open an input with FFmpeg, quietly
+/// # Parameters
+/// - `file`: Path or URL of the media
+#[cfg(feature = "video")]
+pub fn open_input(file: &str) -> Result<format::context::Input, String> {
+    ffmpeg::init().map_err(|e| format!("Failed to initialize FFmpeg: {}", e))?;
+    ffmpeg::util::log::set_level(ffmpeg::util::log::Level::Quiet);
+    format::input(&file).map_err(|e| format!("Failed to open media: {}", e))
+}
+
+/// Describe the streams of an opened input
+/// # Parameters
+/// - `ictx`: Opened input
+#[cfg(feature = "video")]
+pub fn streams(ictx: &format::context::Input) -> Vec<StreamInfo> {
+    let container_duration = ictx.duration();
+    let mut counts = Vec::new(); // Streams seen per type
+    ictx.streams().map(|stream| {
+        let parameters = stream.parameters();
+        let kind = parameters.medium();
+        let track = counts.iter().filter(|&&k| k == kind).count();
+        counts.push(kind);
+
+        let mut info = StreamInfo {
+            index: stream.index(),
+            kind,
+            track,
+            codec: parameters.id().name().to_string(),
+            size: None,
+            pixel_format: None,
+            frame_rate: None,
+            frames: None,
+            sample_rate: None,
+            channels: None,
+            language: stream.metadata().get("language").map(String::from),
+            duration: None,
+        };
+
+        // Codec properties are read from an opened decoder
+        let decoder = codec::context::Context::from_parameters(stream.parameters())
+            .map(|context| context.decoder());
+        match (kind, decoder) {
+            (media::Type::Video, Ok(decoder)) => if let Ok(video) = decoder.video() {
+                info.size = Some((video.width(), video.height()));
+                info.pixel_format = video.format().descriptor().map(|d| d.name().to_string());
+            },
+            (media::Type::Audio, Ok(decoder)) => if let Ok(audio) = decoder.audio() {
+                info.sample_rate = Some(audio.rate());
+                info.channels = Some(audio.channels());
+            },
+            _ => (),
+        }
+        if kind == media::Type::Video {
+            let rate = stream.avg_frame_rate();
+            if rate.0 > 0 && rate.1 > 0 {
+                info.frame_rate = Some(rate.0 as f64 / rate.1 as f64);
+            }
+            if stream.frames() > 0 {
+                info.frames = Some(stream.frames() as u64);
+            }
+        }
+
+        let time_base = stream.time_base();
+        info.duration = if stream.duration() > 0 && time_base.1 > 0 {
+            Some(stream.duration() as f64 * time_base.0 as f64 / time_base.1 as f64)
+        } else if container_duration > 0 {
+            Some(container_duration as f64 / 1_000_000.0) // AV_TIME_BASE units
+        } else {
+            None
+        };
+        info
+    }).collect()
+}
+
+/// One line description of a stream
+/// # Parameters
+/// - `info`: Stream to describe
+#[cfg(feature = "video")]
+fn describe_stream(info: &StreamInfo) -> String {
+    let mut details = vec![info.codec.clone()];
+    if let Some((w, h)) = info.size {
+        details.push(format!("{}x{}", w, h));
+    }
+    if let Some(pixel_format) = &info.pixel_format {
+        details.push(pixel_format.clone());
+    }
+    if let Some(rate) = info.frame_rate {
+        details.push(format!("{} fps", (rate * 1000.0).round() / 1000.0));
+    }
+    if let Some(frames) = info.frames {
+        details.push(format!("{} frames", frames));
+    }
+    if let Some(rate) = info.sample_rate {
+        details.push(format!("{} Hz", rate));
+    }
+    if let Some(channels) = info.channels {
+        details.push(format!("{} channels", channels));
+    }
+    if let Some(language) = &info.language {
+        details.push(language.clone());
+    }
+    if let Some(duration) = info.duration {
+        details.push(format_duration(duration));
+    }
+    format!("#{} {} {}: {}", info.index, kind_name(info.kind), info.track, details.join(", "))
+}
+
+/// Print the streams of a media, one line per stream
+/// # Parameters
+/// - `file`: Path or URL of the media
+#[cfg(feature = "video")]
+pub fn print_tracks(file: &str) -> Result<(), String> {
+    let ictx = open_input(file)?;
+    println!("{}", file);
+    for info in streams(&ictx) {
+        println!("  {}", describe_stream(&info));
+    }
+    Ok(())
+}
+
+/// Builds without the `video` feature cannot open videos
+#[cfg(not(feature = "video"))]
+pub fn print_tracks(_file: &str) -> Result<(), String> {
+    Err(NO_VIDEO.to_string())
+}
+
+/// Number of frames of an image, more than one for animated GIF and APNG
+/// (animated WebP is described by `webp_info`)
+/// # Parameters
+/// - `data`: Encoded image
+/// - `format`: Format of the image
+fn image_frames(data: &[u8], format: image::ImageFormat) -> usize {
+    match format {
+        image::ImageFormat::Gif => GifDecoder::new(Cursor::new(data))
+            .map_or(1, |decoder| decoder.into_frames().count()),
+        image::ImageFormat::Png => PngDecoder::new(Cursor::new(data))
+            .map_or(1, |decoder| if decoder.is_apng() {
+                decoder.apng().into_frames().count()
+            } else {
+                1
+            }),
+        _ => 1,
+    }
+}
+
+/// Describe an image
+/// # Parameters
+/// - `data`: Encoded image
+/// - `format`: Format of the image
+pub fn image_info(data: &[u8], format: image::ImageFormat) -> Result<ImageInfo, String> {
+    let (width, height, color, frames) = if animation::is_animated_webp(data) {
+        webp_info(data)?
+    } else {
+        let image = image::load_from_memory_with_format(data, format).map_err(|e| e.to_string())?;
+        (image.width(), image.height(), format!("{:?}", image.color()), image_frames(data, format))
+    };
+    let exif = exif::Reader::new().read_from_container(&mut Cursor::new(data)).ok()
+        .map(|exif| exif.fields()
+            .filter(|field| field.ifd_num == exif::In::PRIMARY)
+            .map(|field| (field.tag.to_string(), field.display_value().with_unit(&exif).to_string()))
+            .collect())
+        .unwrap_or_default();
+    Ok(ImageInfo {
+        format: format!("{:?}", format),
+        width,
+        height,
+        color,
+        frames,
+        exif,
+    })
+}
+
+/// Dimensions, color type and number of frames of an animated WebP,
+/// which the image crate cannot decode
+/// # Parameters
+/// - `data`: Encoded WebP
+fn webp_info(data: &[u8]) -> Result<(u32, u32, String, usize), String> {
+    let decoder = WebPDecoder::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    let (width, height) = decoder.dimensions();
+    let color = if decoder.has_alpha() { "Rgba8" } else { "Rgb8" };
+    Ok((width, height, color.to_string(), decoder.num_frames() as usize))
+}
+
+/// Describe a media: images are read by the image crate, anything else by FFmpeg
+/// # Parameters
+/// - `file`: Path or URL of the media
+pub fn probe(file: &str) -> Result<MediaInfo, String> {
+    // Sniff the header, only images are read whole
+    let mut header = Vec::new();
+    let format = File::open(file)
+        .and_then(|f| f.take(SNIFF_SIZE).read_to_end(&mut header))
+        .ok()
+        .and_then(|_| image::guess_format(&header).ok());
+    if let Some(format) = format {
+        let data = std::fs::read(file).map_err(|e| e.to_string())?;
+        return image_info(&data, format).map(MediaInfo::Image);
+    }
+    video_info(file)
+}
+
+/// Describe a media opened by FFmpeg
+/// # Parameters
+/// - `file`: Path or URL of the media
+#[cfg(feature = "video")]
+fn video_info(file: &str) -> Result<MediaInfo, String> {
+    let ictx = open_input(file)?;
+    let format = ictx.format();
+    Ok(MediaInfo::Video(VideoInfo {
+        container: format!("{} ({})", format.name(), format.description()),
+        duration: Some(ictx.duration()).filter(|&d| d > 0).map(|d| d as f64 / 1_000_000.0),
+        bit_rate: Some(ictx.bit_rate()).filter(|&rate| rate > 0),
+        streams: streams(&ictx),
+    }))
+}
+
+/// Builds without the `video` feature only describe images
+#[cfg(not(feature = "video"))]
+fn video_info(_file: &str) -> Result<MediaInfo, String> {
+    Err(NO_VIDEO.to_string())
+}
+
+/// JSON value of an optional number
+#[cfg(feature = "video")]
+fn json_number<T: ToString>(value: Option<T>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+/// JSON value of an optional string
+#[cfg(feature = "video")]
+fn json_text(value: Option<&str>) -> String {
+    value.map_or("null".to_string(), json_string)
+}
+
+impl MediaInfo {
+    /// Write the summary for humans
+    /// # Parameters
+    /// - `out`: Destination of the summary
+    /// - `file`: Path of the media, as given
+    pub fn write_text<W: Write>(&self, out: &mut W, file: &str) -> std::io::Result<()> {
+        writeln!(out, "File: {}", file)?;
+        match self {
+            MediaInfo::Image(info) => {
+                writeln!(out, "Format: {}", info.format)?;
+                writeln!(out, "Dimensions: {}x{}", info.width, info.height)?;
+                writeln!(out, "Color: {}", info.color)?;
+                writeln!(out, "Frames: {}", info.frames)?;
+                if !info.exif.is_empty() {
+                    writeln!(out, "EXIF:")?;
+                    for (tag, value) in &info.exif {
+                        writeln!(out, "  {}: {}", tag, value)?;
+                    }
+                }
+            }
+            #[cfg(feature = "video")]
+            MediaInfo::Video(info) => {
+                writeln!(out, "Container: {}", info.container)?;
+                if let Some(duration) = info.duration {
+                    writeln!(out, "Duration: {}", format_duration(duration))?;
+                }
+                if let Some(rate) = info.bit_rate {
+                    writeln!(out, "Bit rate: {} kb/s", rate / 1000)?;
+                }
+                writeln!(out, "Streams:")?;
+                for stream in &info.streams {
+                    writeln!(out, "  {}", describe_stream(stream))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the summary as a JSON object
+    /// # Parameters
+    /// - `out`: Destination of the summary
+    /// - `file`: Path of the media, as given
+    pub fn write_json<W: Write>(&self, out: &mut W, file: &str) -> std::io::Result<()> {
+        write!(out, "{{\"file\": {}, ", json_string(file))?;
+        match self {
+            MediaInfo::Image(info) => {
+                let exif: Vec<String> = info.exif.iter()
+                    .map(|(tag, value)| format!("{}: {}", json_string(tag), json_string(value)))
+                    .collect();
+                write!(out, "\"type\": \"image\", \"format\": {}, \"width\": {}, \"height\": {}, \
+                             \"color\": {}, \"frames\": {}, \"exif\": {{{}}}",
+                       json_string(&info.format), info.width, info.height,
+                       json_string(&info.color), info.frames, exif.join(", "))?;
+            }
+            #[cfg(feature = "video")]
+            MediaInfo::Video(info) => {
+                let streams: Vec<String> = info.streams.iter().map(|stream| format!(
+                    "{{\"index\": {}, \"type\": {}, \"track\": {}, \"codec\": {}, \
+                     \"width\": {}, \"height\": {}, \"pixel_format\": {}, \"frame_rate\": {}, \
+                     \"frames\": {}, \"sample_rate\": {}, \"channels\": {}, \
+                     \"language\": {}, \"duration\": {}}}",
+                    stream.index, json_string(kind_name(stream.kind)), stream.track,
+                    json_string(&stream.codec),
+                    json_number(stream.size.map(|s| s.0)), json_number(stream.size.map(|s| s.1)),
+                    json_text(stream.pixel_format.as_deref()), json_number(stream.frame_rate),
+                    json_number(stream.frames), json_number(stream.sample_rate),
+                    json_number(stream.channels), json_text(stream.language.as_deref()),
+                    json_number(stream.duration))).collect();
+                write!(out, "\"type\": \"video\", \"container\": {}, \"duration\": {}, \
+                             \"bit_rate\": {}, \"streams\": [{}]",
+                       json_string(&info.container), json_number(info.duration),
+                       json_number(info.bit_rate), streams.join(", "))?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
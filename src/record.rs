@@ -0,0 +1,64 @@
+/// record.rs - Record the escape stream of a playback as an asciicast v2 file
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+/// Writer of asciicast v2 files (https://docs.asciinema.org/manual/asciicast/v2/)
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    /// Create the file and write the header
+    /// # Parameters
+    /// - `path`: Path to the .cast file
+    /// - `width`: Width of the recorded terminal in characters
+    /// - `height`: Height of the recorded terminal in lines
+    pub fn create(path: &str, width: u32, height: u32) -> Result<Recorder, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut recorder = Recorder { out: BufWriter::new(file) };
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        writeln!(recorder.out,
+                 "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}, \
+                  \"env\": {{\"TERM\": \"xterm-256color\"}}}}",
+                 width, height, timestamp)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        Ok(recorder)
+    }
+
+    /// Append terminal output, line feeds become CRLF like a terminal driver would
+    /// # Parameters
+    /// - `time`: Seconds since the beginning of the recording
+    /// - `data`: Bytes written to the terminal
+    pub fn output(&mut self, time: f64, data: &[u8]) -> std::io::Result<()> {
+        let text = String::from_utf8_lossy(data).replace('\n', "\r\n");
+        writeln!(self.out, "[{:.6}, \"o\", {}]", time, json_string(&text))
+    }
+
+    /// Flush the events to the file
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Quote a string for JSON
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,406 @@
+/// source.rs - Resolve command line inputs (files, stdin, URLs, video sites) to media
+use image::ImageFormat;
+use regex::Regex;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::io::{AsRawFd, IntoRawFd};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex, OnceLock, atomic::{AtomicBool, Ordering}};
+use std::thread;
+use std::time::Duration;
+
+use crate::animation;
+
+
+/// Extensions played as videos
+pub const SUPPORTED_VIDEOS: [&str; 22] = ["avi", "mp4", "mkv", "m2v",
+                                          "ogg", "ogv", "aac", "aax",
+                                          "mov", "wmv", "avchd", "m4p",
+                                          "f4v", "swf", "mkv", "yuv", "webm",
+                                          "amv", "m4v", "3gp", "3g2", "nsv"];
+
+/// Extensions of images that may be animated, sniffed and decoded by the image crate
+const ANIMATION_FORMATS: [&str; 4] = ["gif", "png", "apng", "webp"];
+
+/// Extensions of still images that FFmpeg can open from a URL
+const SUPPORTED_IMAGES: [&str; 9] = ["png", "jpg", "jpeg", "webp", "bmp", "tif", "tiff",
+                                     "ico", "tga"];
+
+/// Protocols of network streams, played live
+const LIVE_SCHEMES: [&str; 7] = ["rtsp", "rtsps", "rtmp", "rtmps", "udp", "srt", "rtp"];
+
+/// Extensions of HTTP live streaming playlists
+const LIVE_PLAYLISTS: [&str; 2] = ["m3u8", "m3u"];
+
+/// libavdevice formats of capture devices, given as `format:path`
+const DEVICE_FORMATS: [&str; 1] = ["v4l2"];
+
+/// libavdevice format of filtergraph sources, `lavfi:testsrc2`
+const LAVFI_FORMAT: &str = "lavfi";
+
+/// Error of video sites, in builds without yt-dlp
+const NO_YTDLP: &str = "built without yt-dlp support (cargo feature `ytdlp`)";
+
+/// Default yt-dlp format selection
+pub const DEFAULT_YTDL_FORMAT: &str = "best[height<=720][ext=mp4]/best[height<=720]/best";
+
+/// Bytes read from stdin or a file to sniff the media format
+pub const SNIFF_SIZE: u64 = 64;
+
+// Downloads and yt-dlp processes to clean up on exit, with the pipes FFmpeg reads
+// their output from: the `pipe:` protocol does not close them
+static TEMP_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static CHILDREN: Mutex<Vec<(Child, ChildStdout)>> = Mutex::new(Vec::new());
+
+// Compiled once, `Source::parse` runs for every input
+static SITE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Where a media comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    File(String),   // Local path
+    Stdin,          // `-`
+    Url(String),    // Direct link to a media file, opened by FFmpeg
+    Site(String),   // Page of a site supported by yt-dlp (YouTube, Vimeo, Twitch...)
+    Device(String), // libavdevice input as `format:path` (`v4l2:/dev/video0`, `lavfi:testsrc2`)
+}
+
+/// Media ready to be decoded
+#[derive(Debug)]
+pub enum Media {
+    Image(String),      // Path to a still image
+    ImageData(Vec<u8>), // Encoded still image read in memory
+    ImageUrl(String),   // URL of a still image, decoded by FFmpeg
+    Animation(Vec<u8>), // Encoded animated GIF, APNG or WebP, decoded natively
+    Video(String),      // Path or URL of a video, decoded by FFmpeg
+    Stream(String),     // Video that cannot seek: FFmpeg `pipe:` url or lavfi source
+    Live(String),       // URL of a network stream, played as it comes
+    Device(String),     // Capture device as `format:path`, always showing the newest frame
+}
+
+/// Lowercase extension of a path or of the path of a URL
+fn extension(path: &str) -> Option<String> {
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    let ext = path.rsplit_once('.')?.1;
+    if ext.contains('/') {
+        return None;
+    }
+    Some(ext.to_lowercase())
+}
+
+/// Lowercase extension of the path of an http(s) URL
+fn url_extension(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    extension(rest.split_once('/')?.1)
+}
+
+/// Is the URL a network stream (RTSP, RTMP, UDP, SRT or HLS) ?
+/// # Parameters
+/// - `url`: URL given to FFmpeg
+pub fn is_live_url(url: &str) -> bool {
+    match url.split_once("://") {
+        Some((scheme, _)) if LIVE_SCHEMES.contains(&scheme.to_lowercase().as_str()) => true,
+        Some(_) => url_extension(url)
+            .is_some_and(|ext| LIVE_PLAYLISTS.contains(&ext.as_str())),
+        None => false,
+    }
+}
+
+/// Split a libavdevice input into its format and path
+/// # Parameters
+/// - `input`: Input like `v4l2:/dev/video0` or `lavfi:testsrc2=size=640x360`
+pub fn device_input(input: &str) -> Option<(&str, &str)> {
+    let (format, path) = input.split_once(':')?;
+    if (DEVICE_FORMATS.contains(&format) || format == LAVFI_FORMAT) && !path.is_empty() {
+        Some((format, path))
+    } else {
+        None
+    }
+}
+
+/// Is the input a capture device (webcam, capture card) delivering frames in realtime ?
+/// # Parameters
+/// - `input`: Input like `v4l2:/dev/video0`
+pub fn is_capture_device(input: &str) -> bool {
+    device_input(input).is_some_and(|(format, _)| DEVICE_FORMATS.contains(&format))
+}
+
+impl Source {
+    /// Guess the source of a command line input
+    /// # Parameters
+    /// - `input`: Path or URL given on the command line
+    pub fn parse(input: &str) -> Source {
+        // Links without scheme to the sites people paste most
+        let site_regex = SITE_REGEX.get_or_init(|| Regex::new(
+            r"^(www\.|m\.)?(youtube\.com|youtu\.be|vimeo\.com|(clips\.)?twitch\.tv|reddit\.com)/"
+        ).unwrap());
+
+        if input == "-" {
+            Source::Stdin
+        } else if device_input(input).is_some() {
+            Source::Device(input.to_string())
+        } else if is_live_url(input) {
+            Source::Url(input.to_string())
+        } else if input.starts_with("http://") || input.starts_with("https://") {
+            match url_extension(input) {
+                Some(ext) if SUPPORTED_VIDEOS.contains(&ext.as_str())
+                          || SUPPORTED_IMAGES.contains(&ext.as_str())
+                          || ANIMATION_FORMATS.contains(&ext.as_str()) => {
+                    Source::Url(input.to_string())
+                }
+                _ => Source::Site(input.to_string()),
+            }
+        } else if site_regex.is_match(input) && !Path::new(input).exists() {
+            Source::Site(format!("https://{}", input))
+        } else {
+            Source::File(input.to_string())
+        }
+    }
+
+    /// Is it a file that can be reopened (grid, viewer) ?
+    pub fn is_local(&self) -> bool {
+        matches!(self, Source::File(_))
+    }
+
+    /// Resolve to a playable media, streaming or reading it if needed
+    /// # Parameters
+    /// - `ytdl_format`: yt-dlp format selection for video sites
+    /// - `download`: Download videos from sites before playing, to seek and extract audio
+    pub fn resolve(&self, ytdl_format: &str, download: bool) -> Result<Media, String> {
+        match self {
+            Source::File(path) => Ok(media_from_path(path.clone())),
+            Source::Stdin => read_stdin_media(),
+            Source::Url(url) if is_live_url(url) => Ok(Media::Live(url.clone())),
+            Source::Url(url) => {
+                let ext = url_extension(url).unwrap_or_default();
+                if ANIMATION_FORMATS.contains(&ext.as_str()) {
+                    media_from_url(url)
+                } else if SUPPORTED_IMAGES.contains(&ext.as_str()) {
+                    Ok(Media::ImageUrl(url.clone()))
+                } else {
+                    Ok(Media::Video(url.clone()))
+                }
+            }
+            Source::Site(_) if !cfg!(feature = "ytdlp") => Err(NO_YTDLP.to_string()),
+            Source::Site(url) if download => {
+                download_video(url, ytdl_format).map(media_from_path)
+            }
+            Source::Site(url) => stream_video(url, ytdl_format),
+            Source::Device(input) if is_capture_device(input) => Ok(Media::Device(input.clone())),
+            // lavfi sources generate frames on demand, they play like streams
+            Source::Device(input) => Ok(Media::Stream(input.clone())),
+        }
+    }
+}
+
+/// Image, animation or video, from the extension of a file
+/// GIF, PNG and WebP files are read to tell animations from still images
+fn media_from_path(path: String) -> Media {
+    match extension(&path).as_deref() {
+        // Sniff the headers, only animations are loaded in memory
+        Some(ext) if ANIMATION_FORMATS.contains(&ext) && animation::is_animation_file(&path) => {
+            match std::fs::read(&path) {
+                Ok(data) => Media::Animation(data),
+                Err(_) => Media::Image(path), // The image loader reports the error
+            }
+        }
+        Some(ext) if ANIMATION_FORMATS.contains(&ext) => Media::Image(path),
+        Some(ext) if SUPPORTED_VIDEOS.contains(&ext) => Media::Video(path),
+        _ => Media::Image(path),
+    }
+}
+
+/// Image or animation from a link to a GIF, PNG or WebP, read in memory to sniff it
+/// # Parameters
+/// - `url`: Direct link to the file
+fn media_from_url(url: &str) -> Result<Media, String> {
+    let output = Command::new("curl")
+        .args(["-fsSL", url])
+        .output()
+        .map_err(|e| format!("Failed to execute curl: {}. Make sure curl is installed.", e))?;
+    if !output.status.success() {
+        return Err(format!("Failed to download: {}",
+                           String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let data = output.stdout;
+    Ok(if animation::is_animation(&data) {
+        Media::Animation(data)
+    } else if cfg!(feature = "video") && image::guess_format(&data).ok() == Some(ImageFormat::WebP) {
+        Media::ImageUrl(url.to_string()) // The image crate decodes still WebP in grayscale
+    } else {
+        Media::ImageData(data)
+    })
+}
+
+/// Read media from stdin, sniffing the format from the first bytes
+/// Images are loaded in memory, anything else is streamed to FFmpeg
+fn read_stdin_media() -> Result<Media, String> {
+    let mut stdin = std::io::stdin();
+    let mut header = Vec::new();
+    (&mut stdin).take(SNIFF_SIZE).read_to_end(&mut header)
+        .map_err(|e| format!("Failed to read stdin: {}", e))?;
+    if header.is_empty() {
+        return Err("stdin is empty".to_string());
+    }
+
+    // Images and animations: decode the whole buffer
+    if image::guess_format(&header).is_ok() {
+        let mut data = header;
+        stdin.read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+        return Ok(if animation::is_animation(&data) {
+            Media::Animation(data)
+        } else {
+            Media::ImageData(data)
+        });
+    }
+
+    // Video streams: forward the sniffed bytes and the rest of stdin
+    // to a pipe that FFmpeg reads as it plays
+    let (reader, mut writer) = std::io::pipe()
+        .map_err(|e| format!("Failed to create pipe: {}", e))?;
+    thread::spawn(move || {
+        if writer.write_all(&header).is_ok() {
+            std::io::copy(&mut stdin, &mut writer).ok();
+        }
+    });
+    Ok(Media::Stream(format!("pipe:{}", reader.into_raw_fd())))
+}
+
+/// Stream a video with yt-dlp, playback starts while it downloads
+/// # Parameters
+/// - `url`: Page of the video
+/// - `format`: yt-dlp format selection
+fn stream_video(url: &str, format: &str) -> Result<Media, String> {
+    let mut child = Command::new("yt-dlp")
+        .arg("-f")
+        .arg(format)
+        .arg("--no-playlist")
+        .arg("--quiet")
+        .arg("--no-warnings")
+        .arg("--no-progress")
+        .arg("-o")
+        .arg("-")
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute yt-dlp: {}. Make sure yt-dlp is installed.", e))?;
+    let stdout = child.stdout.take().unwrap();
+    let input = format!("pipe:{}", stdout.as_raw_fd());
+    CHILDREN.lock().unwrap().push((child, stdout));
+    Ok(Media::Stream(input))
+}
+
+/// Stop yt-dlp, close its pipes and remove downloaded files
+pub fn clean_temp_files() {
+    for (mut child, _stdout) in CHILDREN.lock().unwrap().drain(..) {
+        child.kill().ok();
+        child.wait().ok();
+    }
+    for dir in TEMP_DIRS.lock().unwrap().drain(..) {
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
+
+/// Stop yt-dlp, remove downloaded files and exit, downloads of the previous inputs included
+/// # Parameters
+/// - `code`: Exit status
+pub fn exit(code: i32) -> ! {
+    clean_temp_files();
+    std::process::exit(code)
+}
+
+/// Download a video with yt-dlp, returns the path to the file
+/// # Parameters
+/// - `url`: Page of the video
+/// - `format`: yt-dlp format selection
+fn download_video(url: &str, format: &str) -> Result<String, String> {
+    println!("Fetching video...");
+
+    // Create a temporary directory for the video, removed by `clean_temp_files`
+    let temp_dir = tempfile::tempdir()
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?
+        .keep();
+    let temp_path = temp_dir.join("video.mp4");
+    TEMP_DIRS.lock().unwrap().push(temp_dir);
+
+    // Start loading animation in a separate thread
+    let loading = Arc::new(AtomicBool::new(true));
+    let loading_clone = Arc::clone(&loading);
+
+    let spinner_thread = thread::spawn(move || {
+        let frames = vec!["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+        let mut idx = 0;
+        while loading_clone.load(Ordering::Relaxed) {
+            print!("\r{} Downloading... ", frames[idx]);
+            std::io::Write::flush(&mut std::io::stdout()).ok();
+            idx = (idx + 1) % frames.len();
+            thread::sleep(Duration::from_millis(80));
+        }
+        print!("\r\x1b[K"); // Clear the line
+        std::io::Write::flush(&mut std::io::stdout()).ok();
+    });
+
+    // Use yt-dlp to download the video with progress output
+    let child_result = Command::new("yt-dlp")
+        .arg("-f")
+        .arg(format)
+        .arg("--no-playlist")
+        .arg("--quiet")
+        .arg("--progress")
+        .arg("--newline")
+        .arg("-o")
+        .arg(&temp_path)
+        .arg(url)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match child_result {
+        Ok(c) => c,
+        Err(e) => {
+            loading.store(false, Ordering::Relaxed);
+            spinner_thread.join().ok();
+            return Err(format!("Failed to execute yt-dlp: {}. Make sure yt-dlp is installed.", e));
+        }
+    };
+
+    // Read stderr in real-time for progress
+    if let Some(stderr) = child.stderr.take() {
+        let reader = BufReader::new(stderr);
+        for line in reader.lines().map_while(Result::ok) {
+            // Show download progress percentage
+            if line.contains("%") && line.contains("of") {
+                print!("\r\x1b[K{}", line.trim());
+                std::io::Write::flush(&mut std::io::stdout()).ok();
+            }
+        }
+    }
+
+    let status = match child.wait() {
+        Ok(s) => s,
+        Err(e) => {
+            loading.store(false, Ordering::Relaxed);
+            spinner_thread.join().ok();
+            return Err(format!("Failed to wait for yt-dlp: {}", e));
+        }
+    };
+
+    // Stop the loading animation
+    loading.store(false, Ordering::Relaxed);
+    spinner_thread.join().ok();
+
+    if !status.success() {
+        return Err(format!("yt-dlp failed with exit code: {:?}", status.code()));
+    }
+
+    if !temp_path.exists() {
+        return Err("Download failed: video file not created".to_string());
+    }
+
+    println!("\r\x1b[K✓ Download complete!");
+
+    Ok(temp_path.to_str().unwrap().to_string())
+}
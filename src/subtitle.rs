@@ -0,0 +1,220 @@
+/// subtitle.rs - Decode text subtitles (SRT, ASS, WebVTT) and lay them out under the video
+#[cfg(feature = "video")]
+use ffmpeg_next as ffmpeg;
+#[cfg(feature = "video")]
+use ffmpeg::{codec, format, media};
+
+
+/// Lines reserved under the video for the subtitles
+pub const SUBTITLE_LINES: u32 = 2;
+
+/// Seconds a cue stays on screen when the stream does not tell
+#[cfg(feature = "video")]
+const DEFAULT_CUE_DURATION: f64 = 5.0;
+
+/// Subtitle track to show: a track of the video, or of a separate file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubtitleTrack {
+    pub file: Option<String>, // Subtitle file, None for the tracks of the video
+    pub index: usize,         // Index among the subtitle streams, from 0
+}
+
+/// Text shown between two times, in seconds from the start of the video
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Cues of a subtitle track
+#[derive(Debug, Clone, Default)]
+pub struct Subtitles {
+    cues: Vec<Cue>,
+}
+
+impl Subtitles {
+    /// Add a cue, cues decoded again after a seek are ignored
+    /// # Parameters
+    /// - `cue`: Cue to add
+    pub fn push(&mut self, cue: Cue) {
+        if !self.cues.contains(&cue) {
+            self.cues.push(cue);
+        }
+    }
+
+    /// Text of the cues shown at a time, one cue per line
+    /// # Parameters
+    /// - `time`: Seconds from the start of the video
+    pub fn active(&self, time: f64) -> String {
+        self.cues.iter()
+            .filter(|cue| cue.start <= time && time < cue.end)
+            .map(|cue| cue.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Decoder of a text subtitle stream
+#[cfg(feature = "video")]
+pub struct SubtitleDecoder {
+    pub index: usize, // Index of the stream in the input
+    decoder: codec::decoder::Subtitle,
+    time_base: f64,   // Seconds per timestamp unit
+    offset: f64,      // Start time of the video, subtracted from the cues
+}
+
+#[cfg(feature = "video")]
+impl SubtitleDecoder {
+    /// Open a subtitle stream of an input
+    /// # Parameters
+    /// - `ictx`: Opened input
+    /// - `track`: Index among the subtitle streams, from 0
+    /// - `offset`: Start time of the video in seconds
+    pub fn open(ictx: &format::context::Input, track: usize,
+                offset: f64) -> Result<SubtitleDecoder, String> {
+        let stream = ictx.streams()
+            .filter(|stream| stream.parameters().medium() == media::Type::Subtitle)
+            .nth(track)
+            .ok_or_else(|| format!("No subtitle track {}", track))?;
+        let time_base = stream.time_base();
+        let mut decoder = codec::context::Context::from_parameters(stream.parameters())
+            .map_err(|e| format!("Failed to create subtitle codec context: {}", e))?
+            .decoder();
+        decoder.set_packet_time_base(time_base);
+        let decoder = decoder.subtitle()
+            .map_err(|e| format!("Failed to create subtitle decoder: {}", e))?;
+        Ok(SubtitleDecoder {
+            index: stream.index(),
+            decoder,
+            time_base: time_base.0 as f64 / time_base.1 as f64,
+            offset,
+        })
+    }
+
+    /// Decode a packet of the stream, bitmap subtitles give no cue
+    /// # Parameters
+    /// - `packet`: Packet of the subtitle stream
+    pub fn decode(&mut self, packet: &ffmpeg::Packet) -> Option<Cue> {
+        let mut subtitle = ffmpeg::codec::subtitle::Subtitle::new();
+        if !self.decoder.decode(packet, &mut subtitle).unwrap_or(false) {
+            return None;
+        }
+        let text = subtitle.rects()
+            .filter_map(|rect| match rect {
+                ffmpeg::codec::subtitle::Rect::Text(text) => Some(text.get().trim().to_string()),
+                ffmpeg::codec::subtitle::Rect::Ass(ass) => Some(ass_text(ass.get())),
+                _ => None,
+            })
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if text.is_empty() {
+            return None;
+        }
+
+        // Display times are relative to the packet, the packet duration is more reliable
+        let pts = packet.pts()? as f64 * self.time_base - self.offset;
+        let start = pts + subtitle.start() as f64 / 1000.0;
+        let end = if packet.duration() > 0 {
+            pts + packet.duration() as f64 * self.time_base
+        } else if subtitle.end() > subtitle.start() && subtitle.end() != u32::MAX {
+            pts + subtitle.end() as f64 / 1000.0
+        } else {
+            start + DEFAULT_CUE_DURATION
+        };
+        Some(Cue { start, end, text })
+    }
+}
+
+/// Load all the cues of a subtitle file
+/// # Parameters
+/// - `path`: Path to the file (SRT, ASS, WebVTT...)
+/// - `track`: Index among the subtitle streams of the file, from 0
+#[cfg(feature = "video")]
+pub fn load(path: &str, track: usize) -> Result<Subtitles, String> {
+    let mut ictx = format::input(&path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut decoder = SubtitleDecoder::open(&ictx, track, 0.0)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let mut subtitles = Subtitles::default();
+    for (stream, packet) in ictx.packets() {
+        if stream.index() == decoder.index {
+            if let Some(cue) = decoder.decode(&packet) {
+                subtitles.push(cue);
+            }
+        }
+    }
+    Ok(subtitles)
+}
+
+/// Plain text of an ASS event: its Text field without override tags
+/// # Parameters
+/// - `event`: `ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text`,
+///   or a `Dialogue:` line of older FFmpeg versions
+pub fn ass_text(event: &str) -> String {
+    let text = match event.strip_prefix("Dialogue:") {
+        Some(dialogue) => dialogue.splitn(10, ',').nth(9),
+        None => event.splitn(9, ',').nth(8),
+    }.unwrap_or(event);
+
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => { // Override block: {\i1}, {\pos(10,20)}...
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            '\\' => match chars.peek() {
+                Some('N') | Some('n') => {
+                    chars.next();
+                    plain.push('\n');
+                }
+                Some('h') => {
+                    chars.next();
+                    plain.push(' ');
+                }
+                _ => plain.push(c),
+            },
+            c => plain.push(c),
+        }
+    }
+    plain.lines().map(str::trim).collect::<Vec<_>>().join("\n").trim().to_string()
+}
+
+/// Lay out a text on a fixed number of lines: wrapped on words, clipped and centered
+/// # Parameters
+/// - `text`: Text of the active cues
+/// - `width`: Width of the lines in characters
+/// - `lines`: Number of lines, blank lines fill the rest
+pub fn layout(text: &str, width: usize, lines: usize) -> Vec<String> {
+    let mut wrapped = Vec::new();
+    for line in text.lines() {
+        let mut current = String::new();
+        for word in line.split_whitespace() {
+            let len = current.chars().count();
+            if len > 0 && len + 1 + word.chars().count() > width {
+                wrapped.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        if !current.is_empty() {
+            wrapped.push(current);
+        }
+    }
+
+    (0..lines).map(|i| match wrapped.get(i) {
+        Some(line) => {
+            let line: String = line.chars().take(width).collect();
+            let pad = (width - line.chars().count()) / 2;
+            format!("{}{}", " ".repeat(pad), line)
+        }
+        None => String::new(),
+    }).collect()
+}
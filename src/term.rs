@@ -0,0 +1,185 @@
+/// term.rs - Raw keyboard input and terminal state restoration
+use std::fs::OpenOptions;
+use std::io::{stdin, stdout, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+
+// Terminal attributes saved before switching to raw mode
+static SAVED_TERMIOS: Mutex<Option<libc::termios>> = Mutex::new(None);
+// Are we drawing on the alternate screen ?
+static ALT_SCREEN: AtomicBool = AtomicBool::new(false);
+// Height/width ratio of a character cell (f64 bits), 0 until known
+static CELL_RATIO: AtomicU64 = AtomicU64::new(0);
+
+/// Cell ratio assumed when the terminal does not report its geometry
+pub const DEFAULT_CELL_RATIO: f64 = 2.0;
+
+/// Key presses understood by the interactive modes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Escape,
+    Char(char),
+    Other,
+}
+
+/// Switch to the alternate screen and raw input (no echo, no line buffering)
+pub fn enter_raw_mode() -> Result<(), String> {
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return Err("stdin is not a terminal".to_string());
+        }
+        *SAVED_TERMIOS.lock().unwrap() = Some(termios);
+
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+        termios.c_cc[libc::VMIN] = 1;  // Block until a key is pressed
+        termios.c_cc[libc::VTIME] = 0;
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+    }
+    ALT_SCREEN.store(true, Ordering::Relaxed);
+    print!("\x1b[?1049h");    // Switch to alternate screen
+    print!("\x1b[?25l");      // Hide cursor
+    stdout().flush().ok();
+    Ok(())
+}
+
+/// Wait for a key press on stdin
+pub fn read_key() -> Key {
+    let mut buf = [0u8; 8];
+    let n = stdin().read(&mut buf).unwrap_or(0);
+    match &buf[..n] {
+        [0x1b, b'[', b'A', ..] => Key::Up,
+        [0x1b, b'[', b'B', ..] => Key::Down,
+        [0x1b, b'[', b'C', ..] => Key::Right,
+        [0x1b, b'[', b'D', ..] => Key::Left,
+        [0x1b] => Key::Escape,
+        [c, ..] if c.is_ascii() => Key::Char(*c as char),
+        _ => Key::Other,
+    }
+}
+
+/// Restore the terminal: cooked input, cleared screen, cursor and colors
+pub fn restore() {
+    if let Ok(mut saved) = SAVED_TERMIOS.lock() {
+        if let Some(termios) = saved.take() {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+            }
+        }
+    }
+    if ALT_SCREEN.swap(false, Ordering::Relaxed) {
+        print!("\x1b[?1049l"); // Back to the main screen
+    }
+    else {
+        print!("\x1b[2J");     // Clear entire screen
+        print!("\x1b[H");      // Move cursor to home position
+    }
+    print!("\x1b[?25h");       // Show cursor again
+    print!("\x1b[0m");         // Reset all text attributes
+    stdout().flush().ok();
+}
+
+/// Override the height/width ratio of a character cell
+pub fn set_cell_ratio(ratio: f64) {
+    CELL_RATIO.store(ratio.to_bits(), Ordering::Relaxed);
+}
+
+/// Height/width ratio of a character cell, asked to the terminal once
+pub fn cell_ratio() -> f64 {
+    let bits = CELL_RATIO.load(Ordering::Relaxed);
+    if bits != 0 {
+        return f64::from_bits(bits);
+    }
+    let ratio = query_cell_ratio().unwrap_or(DEFAULT_CELL_RATIO);
+    set_cell_ratio(ratio);
+    ratio
+}
+
+/// Compute the cell ratio from the pixel geometry of the terminal
+fn query_cell_ratio() -> Option<f64> {
+    // Window size in pixels, as reported by the kernel
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } == 0;
+    if ok && ws.ws_xpixel > 0 && ws.ws_ypixel > 0 && ws.ws_col > 0 && ws.ws_row > 0 {
+        let cell_w = ws.ws_xpixel as f64 / ws.ws_col as f64;
+        let cell_h = ws.ws_ypixel as f64 / ws.ws_row as f64;
+        return Some(cell_h / cell_w);
+    }
+
+    // Cell size report: CSI 16 t -> CSI 6 ; height ; width t
+    let reply = query("\x1b[16t", b"t")?;
+    let mut fields = reply.trim_start_matches("\x1b[").trim_end_matches('t').split(';');
+    if fields.next()? != "6" {
+        return None;
+    }
+    let cell_h: f64 = fields.next()?.parse().ok()?;
+    let cell_w: f64 = fields.next()?.parse().ok()?;
+    if cell_h > 0.0 && cell_w > 0.0 {
+        Some(cell_h / cell_w)
+    } else {
+        None
+    }
+}
+
+/// Background color of the terminal, asked once with OSC 11
+pub fn background_color() -> Option<(u8, u8, u8)> {
+    static BACKGROUND: OnceLock<Option<(u8, u8, u8)>> = OnceLock::new();
+    *BACKGROUND.get_or_init(query_background_color)
+}
+
+fn query_background_color() -> Option<(u8, u8, u8)> {
+    // Reply: OSC 11 ; rgb:RRRR/GGGG/BBBB, ended by BEL or ST
+    let reply = query("\x1b]11;?\x1b\\", b"\x07\\")?;
+    let start = reply.find("rgb:")? + 4;
+    let spec = reply[start..].trim_end_matches(['\x07', '\x1b', '\\']);
+    let mut channels = spec.split('/').map(|hex| {
+        // Components have 1 to 4 hex digits, scale them to 8 bits
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1u32 << (4 * hex.len().min(4))) - 1;
+        Some((value * 255 / max) as u8)
+    });
+    Some((channels.next()??, channels.next()??, channels.next()??))
+}
+
+/// Send a query escape sequence to the terminal and read its reply
+/// # Parameters
+/// - `request`: Escape sequence to send
+/// - `terminators`: Bytes that can end the reply
+pub fn query(request: &str, terminators: &[u8]) -> Option<String> {
+    if unsafe { libc::isatty(libc::STDOUT_FILENO) } == 0 {
+        return None; // Do not write escape codes into files or pipes
+    }
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    let fd = tty.as_raw_fd();
+
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return None;
+    }
+    let saved = termios;
+    termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+    termios.c_cc[libc::VMIN] = 0;
+    termios.c_cc[libc::VTIME] = 2; // Give up after 200ms of silence
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+
+    let mut reply = Vec::new();
+    if tty.write_all(request.as_bytes()).is_ok() {
+        let mut byte = [0u8; 1];
+        while let Ok(1) = tty.read(&mut byte) {
+            reply.push(byte[0]);
+            if terminators.contains(&byte[0]) {
+                break;
+            }
+        }
+    }
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &saved) };
+
+    let complete = reply.last().is_some_and(|b| terminators.contains(b));
+    if complete { String::from_utf8(reply).ok() } else { None }
+}
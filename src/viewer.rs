@@ -0,0 +1,254 @@
+/// viewer.rs - Interactive image viewer with navigation, zoom and pan
+use std::io::{stdout, Write};
+use std::path::Path;
+
+use image::RgbaImage;
+use image::imageops::crop_imm;
+
+use crate::graphics::{load_image, scale_image, blend_alpha, print_image, print_image_hpm,
+                      Background};
+use crate::probe::{self, ImageInfo};
+use crate::term::{self, Key};
+use crate::adjust::Adjustments;
+
+
+const ZOOM_STEP: f32 = 1.25;
+const MAX_ZOOM: f32 = 64.0;
+const PAN_STEP: f32 = 0.2; // Fraction of the viewport moved by a key press
+const HELP: &str = "←/→ file  +/- zoom  hjkl pan  0 reset  f fit  i info  q quit";
+
+/// How the image fills the screen at zoom 1
+#[derive(Clone, Copy, PartialEq)]
+enum Fit {
+    Screen,
+    Width,
+    Height,
+}
+
+/// State of the viewer
+struct View {
+    index: usize,
+    image: Option<RgbaImage>,
+    zoom: f32,
+    center: (f32, f32), // Center of the viewport in image pixels
+    fit: Fit,
+    info: bool,
+    details: Option<Result<ImageInfo, String>>, // Metadata of the file, read when first shown
+}
+
+impl View {
+    /// Load the image at `index` and reset the zoom
+    fn open(&mut self, files: &[String], index: usize) {
+        self.index = index;
+        self.image = load_image(&files[index]).ok();
+        self.details = None;
+        self.zoom = 1.0;
+        if let Some(img) = &self.image {
+            self.center = (img.width() as f32 / 2.0, img.height() as f32 / 2.0);
+        }
+    }
+
+    /// Visible part of the image: (x, y, w, h) in image pixels and the scale
+    /// # Parameters
+    /// - `vw`, `vh`: Size of the canvas in square units (character widths)
+    fn viewport(&mut self, vw: f32, vh: f32) -> Option<(f32, f32, f32, f32, f32)> {
+        let img = self.image.as_ref()?;
+        let (iw, ih) = (img.width() as f32, img.height() as f32);
+        let base = match self.fit {
+            Fit::Screen => (vw / iw).min(vh / ih),
+            Fit::Width  => vw / iw,
+            Fit::Height => vh / ih,
+        };
+        let scale = base * self.zoom;
+        let w = (vw / scale).min(iw);
+        let h = (vh / scale).min(ih);
+
+        // Keep the viewport inside the image
+        self.center.0 = self.center.0.max(w / 2.0).min(iw - w / 2.0);
+        self.center.1 = self.center.1.max(h / 2.0).min(ih - h / 2.0);
+        Some((self.center.0 - w / 2.0, self.center.1 - h / 2.0, w, h, scale))
+    }
+}
+
+/// Metadata of an image, as `batimg info` reports it
+/// # Parameters
+/// - `file`: Path to the image
+fn image_details(file: &str) -> Result<ImageInfo, String> {
+    let data = std::fs::read(file).map_err(|e| e.to_string())?;
+    let format = image::guess_format(&data).map_err(|e| e.to_string())?;
+    probe::image_info(&data, format)
+}
+
+/// Lines of metadata shown under the status line
+/// # Parameters
+/// - `file`: Path to the current image
+/// - `view`: Viewer state
+/// - `width`: Width of the terminal in characters, longer lines are cut
+fn info_lines(file: &str, view: &View, width: u32) -> Vec<String> {
+    if !view.info {
+        return Vec::new();
+    }
+    let name = Path::new(file).file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let bytes = std::fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+    let mut lines = match &view.details {
+        Some(Ok(info)) => {
+            let mut lines = vec![format!("Name: {}  Format: {}  Dimensions: {}x{}  Color: {}  \
+                                          Frames: {}  Size: {} bytes",
+                                         name, info.format, info.width, info.height, info.color,
+                                         info.frames, bytes)];
+            if !info.exif.is_empty() {
+                let tags: Vec<String> = info.exif.iter()
+                    .map(|(tag, value)| format!("{}: {}", tag, value))
+                    .collect();
+                lines.push(format!("EXIF: {}", tags.join("  ")));
+            }
+            lines
+        }
+        Some(Err(e)) => vec![format!("Name: {}  Size: {} bytes  ({})", name, bytes, e)],
+        None => vec![format!("Name: {}  Size: {} bytes", name, bytes)],
+    };
+    for line in &mut lines {
+        *line = line.chars().take(width as usize).collect();
+    }
+    lines
+}
+
+/// Print the status line and, on demand, the metadata of the current file
+fn print_status(files: &[String], view: &View, info: &[String]) {
+    let file = &files[view.index];
+    let mut status = format!("[{}/{}] {}", view.index + 1, files.len(), file);
+    if let Some(img) = &view.image {
+        let fit = match view.fit {
+            Fit::Screen => "fit",
+            Fit::Width  => "fit width",
+            Fit::Height => "fit height",
+        };
+        status.push_str(&format!("  {}x{}  {:.0}% {}",
+                                 img.width(), img.height(), view.zoom * 100.0, fit));
+    }
+    print!("\x1b[0m{}  \x1b[2m{}\x1b[0m", status, HELP);
+
+    for line in info {
+        print!("\n\x1b[K{}", line);
+    }
+    stdout().flush().ok();
+}
+
+/// Draw the visible part of the current image
+/// # Parameters
+/// - `view`: Viewer state
+/// - `width`: Width of the canvas in characters
+/// - `height`: Height of the canvas in characters
+/// - `res`: Are we using the half pixel mode ?
+/// - `bg`: Background for semi-transparent pixels
+/// - `adjust`: Color adjustments
+fn draw(view: &mut View, width: u32, height: u32, res: bool, bg: Background,
+        adjust: &Adjustments) {
+    let cell_ratio = term::cell_ratio() as f32;
+    let (vw, vh) = (width as f32, height as f32 * cell_ratio);
+    let (x, y, w, h, scale) = match view.viewport(vw, vh) {
+        Some(viewport) => viewport,
+        None => {
+            println!("Unknown file format.");
+            return;
+        }
+    };
+    let img = view.image.as_ref().unwrap();
+    let crop = crop_imm(img, x as u32, y as u32,
+                        (w as u32).max(1), (h as u32).max(1)).to_image();
+    // Convert the output size from cell widths to pixels
+    let out_w = ((w * scale) as u32).max(1);
+    let out_h = if res {
+        ((h * scale * 2.0 / cell_ratio) as u32).max(1)
+    } else {
+        ((h * scale / cell_ratio) as u32).max(1)
+    };
+    let mut out = scale_image(&crop, out_w, out_h);
+    adjust.apply(&mut out);
+    blend_alpha(&mut out, bg);
+    if res {
+        print_image_hpm(out);
+    }
+    else {
+        print_image(out);
+    }
+}
+
+/// Browse images full screen with the keyboard
+/// # Parameters
+/// - `files`: Paths to the images
+/// - `width`: Width of the terminal in characters
+/// - `height`: Height of the terminal in characters
+/// - `res`: Are we using the half pixel mode ?
+/// - `bg`: Background for semi-transparent pixels
+/// - `adjust`: Color adjustments
+pub fn view_images(files: &[String], width: u32, height: u32, res: bool,
+                   bg: Background, adjust: &Adjustments) {
+    if files.is_empty() {
+        return;
+    }
+    if let Err(e) = term::enter_raw_mode() {
+        eprintln!("<--view> {}", e);
+        std::process::exit(9);
+    }
+
+    let mut view = View {
+        index: 0,
+        image: None,
+        zoom: 1.0,
+        center: (0.0, 0.0),
+        fit: Fit::Screen,
+        info: false,
+        details: None,
+    };
+    view.open(files, 0);
+
+    loop {
+        // Keep lines for the status (and metadata) below the image
+        if view.info && view.details.is_none() {
+            view.details = Some(image_details(&files[view.index]));
+        }
+        let info = info_lines(&files[view.index], &view, width);
+        let canvas_h = height.saturating_sub(1 + info.len() as u32).max(1);
+        print!("\x1b[H\x1b[2J");
+        draw(&mut view, width, canvas_h, res, bg, adjust);
+        print_status(files, &view, &info);
+
+        let (vw, vh) = (width as f32, canvas_h as f32 * term::cell_ratio() as f32);
+        let pan = view.viewport(vw, vh)
+            .map(|(_, _, w, h, _)| (w * PAN_STEP, h * PAN_STEP))
+            .unwrap_or((0.0, 0.0));
+        match term::read_key() {
+            Key::Char('q') | Key::Escape => break,
+            Key::Right | Key::Char('n') | Key::Char(' ') => {
+                view.open(files, (view.index + 1) % files.len());
+            }
+            Key::Left | Key::Char('p') => {
+                view.open(files, (view.index + files.len() - 1) % files.len());
+            }
+            Key::Char('+') | Key::Char('=') => {
+                view.zoom = (view.zoom * ZOOM_STEP).min(MAX_ZOOM);
+            }
+            Key::Char('-') => view.zoom = (view.zoom / ZOOM_STEP).max(1.0),
+            Key::Char('0') => view.zoom = 1.0,
+            Key::Char('h') => view.center.0 -= pan.0,
+            Key::Char('l') => view.center.0 += pan.0,
+            Key::Up | Key::Char('k') => view.center.1 -= pan.1,
+            Key::Down | Key::Char('j') => view.center.1 += pan.1,
+            Key::Char('f') => {
+                view.fit = match view.fit {
+                    Fit::Screen => Fit::Width,
+                    Fit::Width  => Fit::Height,
+                    Fit::Height => Fit::Screen,
+                };
+                view.zoom = 1.0;
+            }
+            Key::Char('i') => view.info = !view.info,
+            _ => (),
+        }
+    }
+
+    term::restore();
+}
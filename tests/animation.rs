@@ -0,0 +1,119 @@
+// Frames, delays and loop counts of animated GIF, APNG and WebP, decoded without FFmpeg
+use image::Rgba;
+
+use batimg::adjust::Adjustments;
+use batimg::animation::{decode, dump_frames, is_animated_webp, is_animation, is_animation_file,
+                        play_count};
+use batimg::graphics::{Background, Fit, RenderOptions};
+
+mod common;
+use common::{fixture, read_fixture};
+
+
+#[test]
+fn detect_animations() {
+    assert!(is_animation(&read_fixture("anim_2x2.gif")));
+    assert!(is_animation(&read_fixture("anim_2x2.png")));
+    assert!(is_animation(&read_fixture("anim_2x2.webp")));
+    assert!(!is_animation(&read_fixture("odd_3x3.png")));
+    assert!(!is_animation(&read_fixture("static_2x2.gif"))); // Single image, with a loop extension
+    assert!(!is_animation(&read_fixture("anim_2x2.gif")[..0x40])); // Truncated in the first image
+
+    // Files are sniffed without loading them
+    assert!(is_animation_file(&fixture("anim_2x2.gif")));
+    assert!(is_animation_file(&fixture("anim_2x2.png")));
+    assert!(is_animation_file(&fixture("anim_2x2.webp")));
+    assert!(!is_animation_file(&fixture("static_2x2.gif")));
+    assert!(!is_animation_file(&fixture("missing.gif")));
+
+    // RIFF header and VP8X chunk, with and without the animation flag
+    let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\x02".to_vec();
+    assert!(is_animated_webp(&webp));
+    webp[20] = 0x10; // Alpha only
+    assert!(!is_animated_webp(&webp));
+}
+
+#[test]
+fn gif_frames() {
+    let data = read_fixture("anim_2x2.gif");
+    assert_eq!(play_count(&data), Some(3)); // Repeated twice after the first play
+    let animation = decode(&data).unwrap();
+    let delays: Vec<f64> = animation.frames.iter().map(|(_, delay)| *delay).collect();
+    assert_eq!(delays, [0.2, 0.1]); // No delay shows for 100ms, like browsers
+
+    // The second frame only draws its pixel over the kept first frame
+    let (frame, _) = &animation.frames[1];
+    assert_eq!(*frame.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+    assert_eq!(*frame.get_pixel(1, 1), Rgba([0, 0, 255, 255]));
+}
+
+#[test]
+fn apng_frames() {
+    let data = read_fixture("anim_2x2.png");
+    assert_eq!(play_count(&data), None); // Plays forever
+    let animation = decode(&data).unwrap();
+    let delays: Vec<f64> = animation.frames.iter().map(|(_, delay)| *delay).collect();
+    assert_eq!(delays, [0.5, 0.25]);
+
+    let (frame, _) = &animation.frames[1];
+    assert_eq!(*frame.get_pixel(0, 1), Rgba([255, 0, 0, 255]));
+    assert_eq!(*frame.get_pixel(1, 1), Rgba([0, 255, 0, 255]));
+}
+
+#[test]
+fn webp_frames() {
+    let data = read_fixture("anim_2x2.webp");
+    assert_eq!(play_count(&data), Some(2));
+    let animation = decode(&data).unwrap();
+    assert_eq!(animation.plays, Some(2));
+    let delays: Vec<f64> = animation.frames.iter().map(|(_, delay)| *delay).collect();
+    assert_eq!(delays, [0.2, 0.1]);
+
+    // Opaque frames are decoded as RGB, the second one replaces a single pixel
+    let (frame, _) = &animation.frames[1];
+    assert_eq!(*frame.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+    assert_eq!(*frame.get_pixel(1, 1), Rgba([255, 0, 0, 255]));
+}
+
+#[test]
+fn frame_at_time() {
+    let animation = decode(&read_fixture("anim_2x2.gif")).unwrap();
+    let second = &animation.frames[1].0;
+    assert_ne!(animation.frame_at(0.0), second);
+    assert_ne!(animation.frame_at(0.19), second);
+    assert_eq!(animation.frame_at(0.2), second);
+    assert_eq!(animation.frame_at(60.0), second); // Past the end
+}
+
+#[test]
+fn dump_animation_frames() {
+    let animation = decode(&read_fixture("anim_2x2.png")).unwrap();
+    let render = RenderOptions {
+        width: 2,
+        height: 2,
+        res: true,
+        fit: Fit::Stretch,
+        bg: Background::Checkerboard,
+        adjust: Adjustments::default(),
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().to_string_lossy().into_owned();
+    dump_frames(&animation, &render, &path, 1..5).unwrap();
+    assert!(!dir.path().join("frame_000000.ans").exists());
+    assert!(dir.path().join("frame_000001.ans").exists());
+    assert!(!dir.path().join("frame_000002.ans").exists()); // Two frames only
+}
+
+#[test]
+fn segments() {
+    let animation = decode(&read_fixture("anim_2x2.png")).unwrap(); // 0.5s then 0.25s
+    assert_eq!(animation.duration(), 0.75);
+    let shown = |start, end| -> Vec<f64> {
+        animation.segment(start, end).iter().map(|(_, shown)| *shown).collect()
+    };
+    assert_eq!(shown(0.0, None), [0.5, 0.25]);
+    assert_eq!(shown(0.25, None), [0.25, 0.25]); // Starts on the first frame, cut
+    assert_eq!(shown(0.0, Some(0.5)), [0.5]);
+    assert_eq!(shown(0.5, Some(0.625)), [0.125]);
+    assert!(shown(1.0, None).is_empty());
+}
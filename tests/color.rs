@@ -0,0 +1,33 @@
+// Gamma-correct downscaling of still images
+use image::{Rgba, RgbaImage};
+
+use batimg::color::resample_linear;
+
+
+/// Black and white pixels average to the sRGB value of half the light
+#[test]
+fn checkerboard_averages_in_linear_light() {
+    // Sides that are not multiples of the target give smaller boxes on the edges
+    let image = RgbaImage::from_fn(1001, 701, |x, y| {
+        if (x + y) % 2 == 0 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) }
+    });
+    let resized = resample_linear(&image, 10, 7);
+    assert_eq!(resized.dimensions(), (10, 7));
+    for pixel in resized.pixels() {
+        assert!(pixel[0].abs_diff(188) <= 2, "{:?}", pixel);
+        assert_eq!(pixel[0], pixel[1]);
+        assert_eq!(pixel[3], 255);
+    }
+}
+
+/// Transparent pixels do not darken the opaque ones they are averaged with
+#[test]
+fn transparent_pixels_keep_colors() {
+    let image = RgbaImage::from_fn(400, 400, |x, _| {
+        if x % 2 == 0 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 0, 0]) }
+    });
+    for pixel in resample_linear(&image, 4, 4).pixels() {
+        assert_eq!(pixel[0], 255);
+        assert!(pixel[3].abs_diff(128) <= 1, "{:?}", pixel);
+    }
+}
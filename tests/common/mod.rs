@@ -0,0 +1,20 @@
+// Helpers shared by the integration tests
+#![allow(dead_code)] // Each test crate uses some of them
+use std::fs;
+use std::path::{Path, PathBuf};
+
+
+/// Directory of the integration tests
+pub fn tests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// Path to a file of `tests/fixtures`
+pub fn fixture(name: &str) -> String {
+    tests_dir().join("fixtures").join(name).to_string_lossy().into_owned()
+}
+
+/// Content of a file of `tests/fixtures`
+pub fn read_fixture(name: &str) -> Vec<u8> {
+    fs::read(fixture(name)).unwrap_or_else(|e| panic!("{}: {}", name, e))
+}
@@ -0,0 +1,106 @@
+// Compare the ANSI output of the renderers with checked-in golden files
+// Run with BATIMG_BLESS=1 to regenerate the golden files after an intended change
+use std::fs;
+
+use image::RgbaImage;
+
+use batimg::graphics::{load_image, write_image, write_image_hpm};
+
+mod common;
+use common::{fixture, tests_dir};
+
+/// Render a fixture with one of the writers
+fn render(name: &str, hpm: bool) -> Vec<u8> {
+    let image = load_image(&fixture(name))
+        .unwrap_or_else(|e| panic!("{}: {}", name, e));
+    let mut out = Vec::new();
+    if hpm {
+        write_image_hpm(&mut out, &image).unwrap();
+    } else {
+        write_image(&mut out, &image).unwrap();
+    }
+    out
+}
+
+/// Compare an output with its golden file
+fn check_golden(golden: &str, output: &[u8]) {
+    let path = tests_dir().join("golden").join(golden);
+    if std::env::var_os("BATIMG_BLESS").is_some() {
+        fs::write(&path, output).unwrap();
+        return;
+    }
+    let expected = fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", golden, e));
+    assert_eq!(String::from_utf8_lossy(output), String::from_utf8_lossy(&expected),
+               "output differs from {}", golden);
+}
+
+#[test]
+fn full_opaque() {
+    check_golden("opaque_2x2.ans", &render("opaque_2x2.png", false));
+}
+
+#[test]
+fn full_transparent() {
+    check_golden("alpha_2x2.ans", &render("alpha_2x2.png", false));
+}
+
+#[test]
+fn full_single_pixel() {
+    check_golden("pixel_1x1.ans", &render("pixel_1x1.png", false));
+}
+
+#[test]
+fn full_odd_size() {
+    check_golden("odd_3x3.ans", &render("odd_3x3.png", false));
+}
+
+#[test]
+fn hpm_opaque() {
+    check_golden("opaque_2x2.hpm.ans", &render("opaque_2x2.png", true));
+}
+
+#[test]
+fn hpm_transparent() {
+    check_golden("alpha_2x2.hpm.ans", &render("alpha_2x2.png", true));
+}
+
+#[test]
+fn hpm_half_blocks() {
+    // Both halves opaque, upper transparent, lower transparent, both transparent
+    check_golden("halfblocks_4x2.hpm.ans", &render("halfblocks_4x2.png", true));
+}
+
+#[test]
+fn hpm_single_pixel() {
+    check_golden("pixel_1x1.hpm.ans", &render("pixel_1x1.png", true));
+}
+
+#[test]
+fn hpm_single_row() {
+    check_golden("row_3x1.hpm.ans", &render("row_3x1.png", true));
+}
+
+#[test]
+fn hpm_odd_height() {
+    // The last row is drawn on the upper half of the cells
+    check_golden("odd_3x3.hpm.ans", &render("odd_3x3.png", true));
+}
+
+#[test]
+fn empty_images() {
+    // Zero rows print nothing, zero columns print empty lines
+    let cases: [(u32, u32, &[u8], &[u8]); 3] = [
+        (0, 0, b"", b""),
+        (3, 0, b"", b""),
+        (0, 3, b"\x1b[0m\n\x1b[0m\n\x1b[0m\n", b"\x1b[0m\n\x1b[0m\n"),
+    ];
+    for (w, h, full, hpm) in cases {
+        let image = RgbaImage::new(w, h);
+        let mut out = Vec::new();
+        write_image(&mut out, &image).unwrap();
+        assert_eq!(out, full, "{}x{}", w, h);
+        out.clear();
+        write_image_hpm(&mut out, &image).unwrap();
+        assert_eq!(out, hpm, "{}x{} half pixel", w, h);
+    }
+}
@@ -0,0 +1,58 @@
+// Media summaries of `batimg info` and streams printed by <--list-tracks>
+use batimg::probe::{format_duration, image_info, probe, MediaInfo};
+#[cfg(feature = "video")]
+use {batimg::probe::kind_name, ffmpeg_next::media};
+
+mod common;
+use common::fixture;
+
+
+#[test]
+fn durations() {
+    assert_eq!(format_duration(0.0), "00:00:00.00");
+    assert_eq!(format_duration(83.456), "00:01:23.46");
+    assert_eq!(format_duration(3725.5), "01:02:05.50");
+}
+
+#[test]
+#[cfg(feature = "video")]
+fn stream_kinds() {
+    assert_eq!(kind_name(media::Type::Video), "video");
+    assert_eq!(kind_name(media::Type::Audio), "audio");
+    assert_eq!(kind_name(media::Type::Subtitle), "subtitle");
+    assert_eq!(kind_name(media::Type::Unknown), "unknown");
+}
+
+#[test]
+fn image_summary() {
+    let info = probe(&fixture("odd_3x3.png")).unwrap();
+    let mut text = Vec::new();
+    info.write_text(&mut text, "odd_3x3.png").unwrap();
+    assert_eq!(String::from_utf8(text).unwrap(),
+               "File: odd_3x3.png\nFormat: Png\nDimensions: 3x3\nColor: Rgba8\nFrames: 1\n");
+
+    let mut json = Vec::new();
+    info.write_json(&mut json, "odd_3x3.png").unwrap();
+    assert_eq!(String::from_utf8(json).unwrap(),
+               "{\"file\": \"odd_3x3.png\", \"type\": \"image\", \"format\": \"Png\", \
+                \"width\": 3, \"height\": 3, \"color\": \"Rgba8\", \"frames\": 1, \"exif\": {}}\n");
+    assert!(matches!(info, MediaInfo::Image(_)));
+}
+
+#[test]
+fn animated_webp_summary() {
+    let info = probe(&fixture("anim_2x2.webp")).unwrap();
+    let mut text = Vec::new();
+    info.write_text(&mut text, "anim_2x2.webp").unwrap();
+    assert_eq!(String::from_utf8(text).unwrap(),
+               "File: anim_2x2.webp\nFormat: WebP\nDimensions: 2x2\nColor: Rgb8\nFrames: 2\n");
+}
+
+/// The viewer describes the bytes it read like `batimg info` describes the file
+#[test]
+fn image_info_matches_probe() {
+    let data = std::fs::read(fixture("anim_2x2.gif")).unwrap();
+    let info = image_info(&data, image::ImageFormat::Gif).unwrap();
+    assert_eq!((info.width, info.height, info.frames), (2, 2, 2));
+    assert_eq!(probe(&fixture("anim_2x2.gif")).unwrap(), MediaInfo::Image(info));
+}
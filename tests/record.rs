@@ -0,0 +1,19 @@
+// Events of the asciicast recordings
+use std::fs;
+
+use batimg::record::Recorder;
+
+
+#[test]
+fn line_feeds_return_the_carriage() {
+    // Players move the cursor down on LF only, rows would start where the previous one ended
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("video.cast");
+    let mut recorder = Recorder::create(path.to_str().unwrap(), 4, 3).unwrap();
+    recorder.output(0.5, b"\x1b[0mab\n\x1b[0mcd\n\x1b[2F").unwrap();
+    recorder.finish().unwrap();
+
+    let cast = fs::read_to_string(&path).unwrap();
+    let events: Vec<&str> = cast.lines().skip(1).collect(); // After the header
+    assert_eq!(events, ["[0.500000, \"o\", \"\\u001b[0mab\\r\\n\\u001b[0mcd\\r\\n\\u001b[2F\"]"]);
+}
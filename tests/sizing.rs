@@ -0,0 +1,71 @@
+// Sizing math of images and video frames
+use image::RgbaImage;
+
+use batimg::graphics::{fit_dimensions, fit_image, frame_layout, Fit};
+use batimg::term;
+
+
+/// Use square pixels in half pixel mode, like most terminals
+fn setup() {
+    term::set_cell_ratio(2.0);
+}
+
+#[test]
+fn contain_wide_picture() {
+    setup();
+    assert_eq!(fit_dimensions(100, 50, 80, 24, true, Fit::Contain), (80, 40));
+    assert_eq!(fit_dimensions(100, 50, 80, 24, false, Fit::Contain), (80, 20));
+}
+
+#[test]
+fn contain_tall_picture() {
+    setup();
+    assert_eq!(fit_dimensions(10, 100, 80, 24, true, Fit::Contain), (4, 48));
+    assert_eq!(fit_dimensions(10, 100, 80, 24, false, Fit::Contain), (4, 24));
+}
+
+#[test]
+fn cover_stretch_and_none() {
+    setup();
+    assert_eq!(fit_dimensions(100, 50, 80, 24, true, Fit::Cover), (96, 48));
+    assert_eq!(fit_dimensions(100, 50, 80, 24, true, Fit::Stretch), (80, 48));
+    assert_eq!(fit_dimensions(100, 50, 80, 24, true, Fit::None), (100, 50));
+}
+
+#[test]
+fn empty_picture() {
+    setup();
+    assert_eq!(fit_dimensions(0, 50, 80, 24, true, Fit::Contain), (0, 0));
+    assert_eq!(fit_dimensions(100, 0, 80, 24, false, Fit::Cover), (0, 0));
+}
+
+#[test]
+fn tiny_canvas_keeps_one_pixel() {
+    setup();
+    assert_eq!(fit_dimensions(1000, 1, 10, 10, true, Fit::Contain), (10, 1));
+}
+
+#[test]
+fn video_frames_fit_the_canvas() {
+    setup();
+    // 16:9 video on a 80x24 terminal
+    assert_eq!(frame_layout(1920, 1080, 80, 24, true, Fit::Contain), ((80, 45), (80, 45)));
+    assert_eq!(frame_layout(1920, 1080, 80, 24, false, Fit::Contain), ((80, 22), (80, 22)));
+}
+
+#[test]
+fn video_frames_crop_the_overflow() {
+    setup();
+    assert_eq!(frame_layout(1920, 1080, 40, 40, true, Fit::Cover), ((142, 80), (40, 80)));
+    assert_eq!(frame_layout(1920, 1080, 80, 24, true, Fit::None), ((1920, 1080), (80, 48)));
+}
+
+#[test]
+fn still_images_crop_the_overflow() {
+    setup();
+    let picture = RgbaImage::new(100, 50);
+    assert_eq!(fit_image(&picture, 80, 24, true, Fit::None).dimensions(), (80, 48));
+    assert_eq!(fit_image(&picture, 80, 24, true, Fit::Cover).dimensions(), (80, 48));
+    assert_eq!(fit_image(&picture, 80, 24, true, Fit::Contain).dimensions(), (80, 40));
+    assert_eq!(fit_image(&picture, 200, 60, true, Fit::None).dimensions(), (100, 50));
+}
@@ -0,0 +1,118 @@
+// Detection of the media sources
+use batimg::source::{device_input, is_capture_device, is_live_url, Media, Source};
+
+mod common;
+use common::fixture;
+
+
+fn site(url: &str) -> Source {
+    Source::Site(url.to_string())
+}
+
+fn url(url: &str) -> Source {
+    Source::Url(url.to_string())
+}
+
+fn file(path: &str) -> Source {
+    Source::File(path.to_string())
+}
+
+#[test]
+fn youtube_links() {
+    for link in ["https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                 "http://youtube.com/watch?v=dQw4w9WgXcQ",
+                 "https://youtu.be/dQw4w9WgXcQ",
+                 "https://www.youtube.com/shorts/abc-DEF_123"] {
+        assert_eq!(Source::parse(link), site(link));
+    }
+    assert_eq!(Source::parse("youtube.com/watch?v=dQw4w9WgXcQ"),
+               site("https://youtube.com/watch?v=dQw4w9WgXcQ"));
+}
+
+#[test]
+fn other_video_sites() {
+    for link in ["https://vimeo.com/76979871",
+                 "https://clips.twitch.tv/SomeClip",
+                 "https://www.reddit.com/r/videos/comments/abc/title/"] {
+        assert_eq!(Source::parse(link), site(link));
+    }
+    assert_eq!(Source::parse("vimeo.com/76979871"), site("https://vimeo.com/76979871"));
+}
+
+#[test]
+fn direct_links() {
+    for link in ["https://example.com/video.mp4",
+                 "http://example.com/media/clip.WEBM?token=abc",
+                 "https://example.com/picture.png#top"] {
+        assert_eq!(Source::parse(link), url(link));
+    }
+    // No media extension: let yt-dlp find the video in the page
+    assert_eq!(Source::parse("https://example.com/watch.php"), site("https://example.com/watch.php"));
+    assert_eq!(Source::parse("https://example.com"), site("https://example.com"));
+}
+
+#[test]
+fn network_streams() {
+    for link in ["rtsp://192.168.1.10:554/stream1",
+                 "rtmp://live.example.com/app/key",
+                 "udp://@239.0.0.1:1234",
+                 "srt://example.com:9000?mode=caller",
+                 "https://example.com/live/index.m3u8"] {
+        assert_eq!(Source::parse(link), url(link));
+        assert!(is_live_url(link), "{}", link);
+    }
+    assert!(!is_live_url("https://example.com/video.mp4"));
+    assert!(!is_live_url("pipe:3"));
+    assert!(!is_live_url("stream.m3u8"));
+}
+
+#[test]
+fn capture_devices() {
+    assert_eq!(Source::parse("v4l2:/dev/video0"), Source::Device("v4l2:/dev/video0".to_string()));
+    assert_eq!(device_input("v4l2:/dev/video0"), Some(("v4l2", "/dev/video0")));
+    assert_eq!(device_input("v4l2:"), None);
+    assert_eq!(device_input("pipe:3"), None);
+    assert!(!Source::parse("v4l2:/dev/video0").is_local());
+    assert!(is_capture_device("v4l2:/dev/video0"));
+}
+
+#[test]
+fn lavfi_sources() {
+    let graph = "lavfi:testsrc2=size=320x240:rate=30";
+    assert_eq!(Source::parse(graph), Source::Device(graph.to_string()));
+    assert_eq!(device_input(graph), Some(("lavfi", "testsrc2=size=320x240:rate=30")));
+    assert!(!is_capture_device(graph));
+    assert!(matches!(Source::parse("lavfi:mandelbrot").resolve("", false),
+                     Ok(Media::Stream(input)) if input == "lavfi:mandelbrot"));
+}
+
+#[test]
+fn local_files_and_stdin() {
+    assert_eq!(Source::parse("-"), Source::Stdin);
+    assert_eq!(Source::parse("video.mp4"), file("video.mp4"));
+    assert_eq!(Source::parse("youtube.mp4"), file("youtube.mp4"));
+    assert_eq!(Source::parse("./dir.v2/picture"), file("./dir.v2/picture"));
+    assert!(Source::parse("img.png").is_local());
+    assert!(!Source::parse("https://youtu.be/dQw4w9WgXcQ").is_local());
+}
+
+#[test]
+fn animations_from_files() {
+    assert!(matches!(file(&fixture("anim_2x2.gif")).resolve("", false), Ok(Media::Animation(_))));
+    assert!(matches!(file(&fixture("anim_2x2.png")).resolve("", false), Ok(Media::Animation(_))));
+    assert!(matches!(file(&fixture("anim_2x2.webp")).resolve("", false), Ok(Media::Animation(_))));
+    // A GIF with a single image is a still, whatever its extensions say
+    assert!(matches!(file(&fixture("static_2x2.gif")).resolve("", false), Ok(Media::Image(_))));
+    assert!(matches!(file(&fixture("odd_3x3.png")).resolve("", false), Ok(Media::Image(_))));
+}
+
+#[test]
+fn animations_from_links() {
+    assert_eq!(Source::parse("https://example.com/cat.gif"), url("https://example.com/cat.gif"));
+    // Read with curl, which opens local files too
+    let link = |name: &str| url(&format!("file://{}", fixture(name)));
+    assert!(matches!(link("anim_2x2.gif").resolve("", false), Ok(Media::Animation(_))));
+    assert!(matches!(link("anim_2x2.webp").resolve("", false), Ok(Media::Animation(_))));
+    assert!(matches!(link("static_2x2.gif").resolve("", false), Ok(Media::ImageData(_))));
+    assert!(link("missing.gif").resolve("", false).is_err());
+}
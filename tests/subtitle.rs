@@ -0,0 +1,38 @@
+// Text of the subtitle cues and their layout under the video
+use batimg::subtitle::{ass_text, layout, Cue, Subtitles};
+
+
+fn cue(start: f64, end: f64, text: &str) -> Cue {
+    Cue { start, end, text: text.to_string() }
+}
+
+#[test]
+fn ass_events_to_text() {
+    assert_eq!(ass_text("0,0,Default,,0,0,0,,Hello, world"), "Hello, world");
+    assert_eq!(ass_text("1,0,Default,,0,0,0,,{\\i1}Two{\\i0}\\Nlines"), "Two\nlines");
+    assert_eq!(ass_text("2,0,Default,,0,0,0,,{\\pos(10,20)}non\\hbreaking"), "non breaking");
+    assert_eq!(ass_text("Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Old format"),
+               "Old format");
+}
+
+#[test]
+fn active_cues() {
+    let mut subtitles = Subtitles::default();
+    subtitles.push(cue(1.0, 3.0, "First"));
+    subtitles.push(cue(2.0, 4.0, "Second"));
+    subtitles.push(cue(1.0, 3.0, "First")); // Decoded again after a loop
+    assert_eq!(subtitles.active(0.5), "");
+    assert_eq!(subtitles.active(1.0), "First");
+    assert_eq!(subtitles.active(2.5), "First\nSecond");
+    assert_eq!(subtitles.active(3.0), "Second");
+    assert_eq!(subtitles.active(4.0), "");
+}
+
+#[test]
+fn layout_centers_and_wraps() {
+    assert_eq!(layout("Hi", 6, 2), vec!["  Hi", ""]);
+    assert_eq!(layout("one two three", 7, 2), vec!["one two", " three"]);
+    assert_eq!(layout("a\nb\nc", 3, 2), vec![" a", " b"]);
+    assert_eq!(layout("", 10, 2), vec!["", ""]);
+    assert_eq!(layout("unbreakable", 4, 1), vec!["unbr"]);
+}